@@ -0,0 +1,183 @@
+use std::f64::consts::PI;
+
+use super::{canvas::Canvas, sky::Sky};
+use crate::primitives::{color::Color, tuple::Tuple, vec3::Vec3};
+
+// radiance arriving from infinitely far away, seen by every ray that leaves the scene
+pub enum Background {
+    Solid(Color),
+    // vertical gradient from straight down to straight up
+    Gradient { bottom: Color, top: Color },
+    // latitude-longitude image, same mapping as the equirectangular camera projection
+    Equirectangular(Canvas),
+    // faces in +x, -x, +y, -y, +z, -z order, each face seen from the inside of the cube
+    CubeMap(Box<[Canvas; 6]>),
+    // analytic daylight, see `Sky`
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Color::black())
+    }
+}
+
+impl Background {
+    pub fn color(&self, direction: Vec3) -> Color {
+        let d = direction.normalize();
+        match self {
+            Background::Solid(c) => *c,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (d.y() + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Background::Equirectangular(image) => {
+                let (u, v) = equirectangular_uv(d);
+                image.sample(u, v)
+            }
+            Background::CubeMap(faces) => {
+                let (face, u, v) = cube_map_uv(d);
+                faces[face].sample(u, v)
+            }
+            Background::Sky(sky) => sky.radiance(d),
+        }
+    }
+}
+
+// inverse of the equirectangular camera projection, (0.5, 0.5) looks down -z
+pub fn equirectangular_uv(d: Vec3) -> (f64, f64) {
+    let longitude = f64::atan2(-d.x(), -d.z());
+    let latitude = d.y().clamp(-1.0, 1.0).asin();
+    (0.5 + longitude / (2.0 * PI), 0.5 - latitude / PI)
+}
+
+pub fn equirectangular_direction(u: f64, v: f64) -> Vec3 {
+    let longitude = (u - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - v) * PI;
+    Vec3::new(
+        -latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+// face index and (u, v) on that face, with v = 0 at the top of the image
+fn cube_map_uv(d: Vec3) -> (usize, f64, f64) {
+    let (x, y, z) = (d.x(), d.y(), d.z());
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    (face, 0.5 * (sc / ma + 1.0), 0.5 * (tc / ma + 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        c.pixels = vec![color; width * height];
+        c
+    }
+
+    #[test]
+    fn default_background_is_black() {
+        let b = Background::default();
+        assert_eq!(b.color(Vec3::new(0.0, 1.0, 0.0)), Color::black());
+    }
+
+    #[test]
+    fn solid_background_ignores_direction() {
+        let b = Background::Solid(Color::new(0.2, 0.3, 0.4));
+        assert_eq!(b.color(Vec3::new(1.0, 0.0, 0.0)), Color::new(0.2, 0.3, 0.4));
+        assert_eq!(
+            b.color(Vec3::new(0.0, -3.0, 1.0)),
+            Color::new(0.2, 0.3, 0.4)
+        );
+    }
+
+    #[test]
+    fn gradient_blends_from_bottom_to_top() {
+        let b = Background::Gradient {
+            bottom: Color::white(),
+            top: Color::blue(),
+        };
+        assert_eq!(b.color(Vec3::new(0.0, 1.0, 0.0)), Color::blue());
+        assert_eq!(b.color(Vec3::new(0.0, -1.0, 0.0)), Color::white());
+        assert_eq!(b.color(Vec3::new(0.0, 0.0, 2.0)), Color::new(0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    fn equirectangular_mapping_round_trips() {
+        for (u, v) in [(0.5, 0.5), (0.1, 0.3), (0.8, 0.9), (0.3, 0.05)] {
+            let (u2, v2) = equirectangular_uv(equirectangular_direction(u, v));
+            assert!((u - u2).abs() < 1e-9 && (v - v2).abs() < 1e-9);
+        }
+        assert_eq!(
+            equirectangular_direction(0.5, 0.5),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn equirectangular_background_looks_up_by_direction() {
+        let mut image = filled(4, 2, Color::black());
+        image[0][2] = Color::red();
+        image[1][2] = Color::green();
+        let b = Background::Equirectangular(image);
+
+        let up_front = equirectangular_direction(0.625, 0.25);
+        let down_front = equirectangular_direction(0.625, 0.75);
+        assert_eq!(b.color(up_front), Color::red());
+        assert_eq!(b.color(down_front), Color::green());
+    }
+
+    #[test]
+    fn cube_map_picks_face_by_major_axis() {
+        let colors = [
+            Color::red(),
+            Color::green(),
+            Color::blue(),
+            Color::white(),
+            Color::new(1.0, 1.0, 0.0),
+            Color::new(0.0, 1.0, 1.0),
+        ];
+        let b = Background::CubeMap(Box::new(colors.map(|c| filled(2, 2, c))));
+
+        assert_eq!(b.color(Vec3::new(1.0, 0.2, -0.3)), colors[0]);
+        assert_eq!(b.color(Vec3::new(-1.0, 0.2, -0.3)), colors[1]);
+        assert_eq!(b.color(Vec3::new(0.1, 1.0, 0.5)), colors[2]);
+        assert_eq!(b.color(Vec3::new(0.1, -1.0, 0.5)), colors[3]);
+        assert_eq!(b.color(Vec3::new(0.1, 0.2, 1.0)), colors[4]);
+        assert_eq!(b.color(Vec3::new(0.1, 0.2, -1.0)), colors[5]);
+    }
+
+    #[test]
+    fn sky_background_evaluates_the_sky_model() {
+        let sky = Sky::new(0.4, 1.2, 3.0);
+        let b = Background::Sky(sky);
+        let d = Vec3::new(0.3, 0.6, -1.0);
+        assert_eq!(b.color(d), sky.radiance(d));
+    }
+
+    #[test]
+    fn cube_map_face_coordinates_start_at_top() {
+        assert_eq!(cube_map_uv(Vec3::new(1.0, 1.0, 0.0)).2, 0.0);
+        assert_eq!(cube_map_uv(Vec3::new(0.0, 0.0, -1.0)), (5, 0.5, 0.5));
+    }
+}
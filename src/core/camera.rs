@@ -1,32 +1,683 @@
-use crate::primitives::matrix4f::Matrix4f;
+use super::{
+    canvas::Canvas,
+    integrator::{Integrator, Whitted},
+    random::Rng,
+    ray::Ray,
+    sampling::{concentric_disk, Supersampling},
+    spectrum::{wavelength_to_color, LAMBDA_MAX, LAMBDA_MIN},
+    world::World,
+};
+use crate::primitives::{color::Color, matrix4f::Matrix4f, point::Point, tuple::Tuple, vec3::Vec3};
+use std::f64::consts::PI;
 
-struct Camera {
+// how film positions are mapped to rays in camera space, the camera looks down -z
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // parallel rays, `width` is the horizontal extent of the view volume in world units
+    Orthographic { width: f64 },
+    // equidistant fisheye, the angle from the view axis grows linearly with the distance from
+    // the image center and reaches fov / 2 at the left and right edges
+    Fisheye,
+    // latitude-longitude panorama covering the whole sphere of directions
+    Equirectangular,
+}
+
+pub struct Camera {
     hsize: f64,
     vsize: f64,
     fov: f64,
     transform: Matrix4f,
+    transform_inverse: Matrix4f,
+    pixel_size: f64,
+    supersampling: Supersampling,
+    // thin lens, a zero aperture radius is the pinhole camera
+    aperture: f64,
+    focal_distance: f64,
+    projection: Projection,
+    // shutter interval, every sample is taken at a random instant inside it
+    shutter_open: f64,
+    shutter_close: f64,
+    // varies every random decision of a render, the same seed reproduces the same image
+    seed: u64,
+    threads: usize,
+    // every sample carries one wavelength and is weighted by its color
+    spectral: bool,
 }
 
 impl Camera {
     pub fn new(hsize: f64, vsize: f64, fov: f64) -> Camera {
+        let half_view = f64::tan(fov / 2.0);
+        let aspect = hsize / vsize;
+        let half_width = if aspect >= 1.0 {
+            half_view
+        } else {
+            half_view * aspect
+        };
+
         Camera {
             hsize,
             vsize,
             fov,
             transform: Matrix4f::identity(),
+            transform_inverse: Matrix4f::identity(),
+            pixel_size: (half_width * 2.0) / hsize,
+            supersampling: Supersampling::default(),
+            aperture: 0.0,
+            focal_distance: 1.0,
+            projection: Projection::Perspective,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            seed: 0,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            spectral: false,
         }
     }
+
+    pub fn set_transformation(&mut self, transformation: Matrix4f) {
+        self.transform = transformation;
+        self.transform_inverse = transformation.invert().unwrap_or(Matrix4f::identity());
+    }
+
+    pub fn set_supersampling(&mut self, supersampling: Supersampling) {
+        self.supersampling = supersampling;
+    }
+
+    pub fn with_supersampling(mut self, supersampling: Supersampling) -> Self {
+        self.supersampling = supersampling;
+        self
+    }
+
+    pub fn set_depth_of_field(&mut self, aperture: f64, focal_distance: f64) {
+        self.aperture = aperture.max(0.0);
+        self.focal_distance = focal_distance;
+    }
+
+    pub fn with_depth_of_field(mut self, aperture: f64, focal_distance: f64) -> Self {
+        self.set_depth_of_field(aperture, focal_distance);
+        self
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter_open = open;
+        self.shutter_close = close;
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.set_shutter(open, close);
+        self
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    // worker threads used by `render`, defaults to the available parallelism
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.set_threads(threads);
+        self
+    }
+
+    pub fn set_spectral(&mut self, spectral: bool) {
+        self.spectral = spectral;
+    }
+
+    pub fn with_spectral(mut self, spectral: bool) -> Self {
+        self.set_spectral(spectral);
+        self
+    }
+
+    pub fn spectral(&self) -> bool {
+        self.spectral
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn hsize(&self) -> f64 {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> f64 {
+        self.vsize
+    }
+
+    pub fn fov(&self) -> f64 {
+        self.fov
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    pub fn transformation(&self) -> &Matrix4f {
+        &self.transform
+    }
+
+    // ray through the center of pixel (px, py)
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
+        self.ray_for_film_point(px as f64 + 0.5, py as f64 + 0.5)
+    }
+
+    // ray through a continuous film position, measured in pixels from the top-left corner
+    pub fn ray_for_film_point(&self, fx: f64, fy: f64) -> Ray {
+        self.ray_through_lens(fx, fy, (0.5, 0.5))
+    }
+
+    // `lens` is a point of the unit square, mapped onto the aperture disk
+    pub fn ray_through_lens(&self, fx: f64, fy: f64, lens: (f64, f64)) -> Ray {
+        let (origin, direction) = self.camera_space_ray(fx, fy, lens);
+        let origin_w = self.transform_inverse * origin;
+        let direction_w = (self.transform_inverse * direction).normalize();
+        Ray::new(origin_w, direction_w)
+    }
+
+    fn camera_space_ray(&self, fx: f64, fy: f64, lens: (f64, f64)) -> (Point, Vec3) {
+        // offset from the image center in pixels, +x is left and +y is up in camera space
+        let sx = self.hsize / 2.0 - fx;
+        let sy = self.vsize / 2.0 - fy;
+
+        match self.projection {
+            Projection::Perspective => {
+                let world_x = sx * self.pixel_size;
+                let world_y = sy * self.pixel_size;
+                let pinhole = Vec3::new(world_x, world_y, -1.0);
+                if self.aperture == 0.0 {
+                    return (Point::zero(), pinhole);
+                }
+
+                let (lx, ly) = concentric_disk(lens.0, lens.1);
+                // the film point pushed out along its pinhole ray until it meets the plane of focus
+                let focus = Point::zero() + pinhole * self.focal_distance;
+                let lens_point = Point::new(lx * self.aperture, ly * self.aperture, 0.0);
+                (lens_point, focus - lens_point)
+            }
+            Projection::Orthographic { width } => {
+                let units_per_pixel = width / self.hsize;
+                let origin = Point::new(sx * units_per_pixel, sy * units_per_pixel, 0.0);
+                (origin, Vec3::new(0.0, 0.0, -1.0))
+            }
+            Projection::Fisheye => {
+                let r = f64::sqrt(sx * sx + sy * sy);
+                if r == 0.0 {
+                    return (Point::zero(), Vec3::new(0.0, 0.0, -1.0));
+                }
+                let theta = r * self.fov / self.hsize;
+                let sin_theta = theta.sin();
+                let direction = Vec3::new(sin_theta * sx / r, sin_theta * sy / r, -f64::cos(theta));
+                (Point::zero(), direction)
+            }
+            Projection::Equirectangular => {
+                let longitude = -sx / self.hsize * 2.0 * PI;
+                let latitude = sy / self.vsize * PI;
+                let direction = Vec3::new(
+                    -latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+                (Point::zero(), direction)
+            }
+        }
+    }
+
+    pub fn color_for_pixel(&self, world: &World, px: usize, py: usize) -> Color {
+        self.color_for_pixel_with(world, &Whitted::default(), px, py)
+    }
+
+    // filtered average of the supersampled radiance around pixel (px, py)
+    pub fn color_for_pixel_with(
+        &self,
+        world: &World,
+        integrator: &dyn Integrator,
+        px: usize,
+        py: usize,
+    ) -> Color {
+        let Supersampling {
+            samples,
+            pattern,
+            filter,
+        } = self.supersampling;
+        let seed = Rng::for_sample(px, py, usize::MAX - 1, self.seed).next_u32();
+        let radius = filter.radius();
+        // wavelength strata are handed out in random order, tying them to the sample index
+        // would tie them to the position inside the pixel for the structured patterns
+        let strata = if self.spectral {
+            shuffled(samples, &mut Rng::for_sample(px, py, usize::MAX, self.seed))
+        } else {
+            (0..samples).collect()
+        };
+
+        let mut sum = Color::black();
+        let mut weight_sum = 0.0;
+        for (i, &slot) in strata.iter().enumerate() {
+            let (u, v) = pattern.point(i, samples, seed);
+            let (dx, dy) = ((u - 0.5) * 2.0 * radius, (v - 0.5) * 2.0 * radius);
+            let weight = filter.weight(dx, dy);
+            if weight == 0.0 {
+                continue;
+            }
+            let mut rng = Rng::for_sample(px, py, i, self.seed);
+            let lens = rng.next_2d();
+            let time =
+                self.shutter_open + (self.shutter_close - self.shutter_open) * rng.next_f64();
+            let ray = self
+                .ray_through_lens(px as f64 + 0.5 + dx, py as f64 + 0.5 + dy, lens)
+                .with_time(time);
+            if self.spectral {
+                // wavelengths stratified over the samples of the pixel
+                let stratum = (slot as f64 + rng.next_f64()) / samples as f64;
+                let wavelength = LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * stratum;
+                let ray = ray.with_wavelength(Some(wavelength));
+                sum += integrator.color_at(world, ray, &mut rng)
+                    * wavelength_to_color(wavelength)
+                    * weight;
+            } else {
+                sum += integrator.color_at(world, ray, &mut rng) * weight;
+            }
+            weight_sum += weight;
+        }
+
+        if weight_sum == 0.0 {
+            return Color::black();
+        }
+        sum * (1.0 / weight_sum)
+    }
+
+    pub fn render(&self, world: &World) -> Canvas {
+        self.render_with(world, &Whitted::default())
+    }
+
+    pub fn render_with(&self, world: &World, integrator: &dyn Integrator) -> Canvas {
+        let mut image = Canvas::new(self.hsize as usize, self.vsize as usize);
+        let (width, height) = (image.width, image.height);
+        let threads = self.threads.min(height).max(1);
+
+        // rows are interleaved between threads, every sample seeds its own generator so the
+        // split does not change the image
+        let rows: Vec<(usize, Vec<Color>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|first| {
+                    scope.spawn(move || {
+                        (first..height)
+                            .step_by(threads)
+                            .map(|y| {
+                                let row = (0..width)
+                                    .map(|x| self.color_for_pixel_with(world, integrator, x, y))
+                                    .collect();
+                                (y, row)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("render thread panicked"))
+                .collect()
+        });
+
+        for (y, row) in rows {
+            for (x, color) in row.into_iter().enumerate() {
+                image.draw_pixel(x, y, color);
+            }
+        }
+        image
+    }
+}
+
+// 0..count in random order
+fn shuffled(count: usize, rng: &mut Rng) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..count).collect();
+    for i in (1..count).rev() {
+        let j = (rng.next_f64() * (i + 1) as f64) as usize;
+        order.swap(i, j.min(i));
+    }
+    order
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
     use super::*;
+    use crate::{
+        core::{
+            background::Background,
+            light::PointLight,
+            object::Object,
+            sampling::{Filter, SamplePattern},
+            transforms::Transformations,
+        },
+        primitives::{float::ApproxEq, vec3::Vec3},
+    };
+
     #[test]
     fn camera_initialization() {
         let camera = Camera::new(200.0, 150.0, 60.0);
         assert_eq!(camera.hsize, 200.0);
-        assert_eq!(camera.vsize, 150.09);
+        assert_eq!(camera.vsize, 150.0);
         assert_eq!(camera.fov, 60.0);
         assert_eq!(camera.transform, Matrix4f::identity());
     }
+
+    #[test]
+    fn pixel_size_for_horizontal_canvas() {
+        let c = Camera::new(200.0, 125.0, FRAC_PI_2);
+        assert!(c.pixel_size().approx_eq(0.01));
+    }
+
+    #[test]
+    fn pixel_size_for_vertical_canvas() {
+        let c = Camera::new(125.0, 200.0, FRAC_PI_2);
+        assert!(c.pixel_size().approx_eq(0.01));
+    }
+
+    #[test]
+    fn ray_through_center_of_canvas() {
+        let c = Camera::new(201.0, 101.0, FRAC_PI_2);
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin(), Point::new(0.0, 0.0, 0.0));
+        assert_eq!(r.direction(), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn ray_through_corner_of_canvas() {
+        let c = Camera::new(201.0, 101.0, FRAC_PI_2);
+        let r = c.ray_for_pixel(0, 0);
+        assert_eq!(r.origin(), Point::new(0.0, 0.0, 0.0));
+        assert_eq!(r.direction(), Vec3::new(0.66519, 0.33259, -0.66851));
+    }
+
+    #[test]
+    fn ray_when_camera_is_transformed() {
+        let mut c = Camera::new(201.0, 101.0, FRAC_PI_2);
+        c.set_transformation(
+            Transformations::rotate_y(FRAC_PI_4) * Transformations::translate(0.0, -2.0, 5.0),
+        );
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin(), Point::new(0.0, 2.0, -5.0));
+        assert_eq!(r.direction(), Vec3::new(SQRT_2 / 2.0, 0.0, -SQRT_2 / 2.0));
+    }
+
+    fn default_world_camera() -> Camera {
+        let mut c = Camera::new(11.0, 11.0, FRAC_PI_2);
+        c.set_transformation(Transformations::view_transform(
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ));
+        c
+    }
+
+    #[test]
+    fn rendering_world_with_camera() {
+        let w = World::default();
+        let c = default_world_camera();
+        let image = c.render(&w);
+        assert_eq!(image[5][5], Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    // integrator that ignores the scene and shows its first random number
+    struct FirstDraw(std::sync::Mutex<Vec<u32>>);
+
+    impl Integrator for FirstDraw {
+        fn color_at(&self, _world: &World, _ray: Ray, rng: &mut Rng) -> Color {
+            let draw = rng.next_u32();
+            self.0.lock().unwrap().push(draw);
+            Color::new(draw as f64, 0.0, 0.0)
+        }
+    }
+
+    fn first_draws(camera: &Camera) -> (Canvas, Vec<u32>) {
+        let integrator = FirstDraw(std::sync::Mutex::new(vec![]));
+        let image = camera.render_with(&World::default(), &integrator);
+        let mut draws = integrator.0.into_inner().unwrap();
+        draws.sort();
+        draws.dedup();
+        (image, draws)
+    }
+
+    #[test]
+    fn rendering_with_another_integrator() {
+        let c = Camera::new(2.0, 2.0, FRAC_PI_2)
+            .with_supersampling(Supersampling::new(4, SamplePattern::Jittered, Filter::Box))
+            .with_threads(1);
+        let (_, draws) = first_draws(&c);
+
+        // every sample of every pixel gets its own random numbers
+        assert_eq!(draws.len(), 16);
+    }
+
+    #[test]
+    fn renders_are_reproducible_across_thread_counts() {
+        let camera = |threads, seed| {
+            Camera::new(5.0, 3.0, FRAC_PI_2)
+                .with_supersampling(Supersampling::new(2, SamplePattern::Jittered, Filter::Box))
+                .with_threads(threads)
+                .with_seed(seed)
+        };
+        let (single, _) = first_draws(&camera(1, 0));
+        let (many, _) = first_draws(&camera(4, 0));
+        let (reseeded, _) = first_draws(&camera(4, 1));
+
+        assert!(single.pixels == many.pixels);
+        assert!(single.pixels != reseeded.pixels);
+    }
+
+    #[test]
+    fn seed_moves_the_jittered_sample_positions() {
+        let w = World::default();
+        // pixel on the silhouette of the sphere, nothing else in the scene is random
+        let mut seen: Vec<Color> = (0..8)
+            .map(|seed| {
+                default_world_camera()
+                    .with_supersampling(Supersampling::new(4, SamplePattern::Jittered, Filter::Box))
+                    .with_seed(seed)
+                    .color_for_pixel(&w, 6, 5)
+            })
+            .collect();
+        seen.sort_by(|a, b| a.r.total_cmp(&b.r));
+        seen.dedup();
+        assert!(seen.len() > 1, "{seen:?}");
+    }
+
+    #[test]
+    fn supersampling_uniform_region_matches_single_sample() {
+        let w = World::default();
+        let c = default_world_camera().with_supersampling(Supersampling::new(
+            16,
+            SamplePattern::Jittered,
+            Filter::Box,
+        ));
+        assert_eq!(c.color_for_pixel(&w, 0, 0), Color::black());
+    }
+
+    #[test]
+    fn spectral_white_stays_white() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::white()));
+        let c = default_world_camera()
+            .with_supersampling(Supersampling::new(64, SamplePattern::Jittered, Filter::Box))
+            .with_spectral(true);
+        let color = c.color_for_pixel(&w, 5, 5);
+        assert!((color.r - 1.0).abs() < 0.05, "{color:?}");
+        assert!((color.g - 1.0).abs() < 0.05, "{color:?}");
+        assert!((color.b - 1.0).abs() < 0.05, "{color:?}");
+    }
+
+    #[test]
+    fn supersampling_blends_silhouette_pixels() {
+        let w = World::default();
+        let single = default_world_camera();
+        let (x, y) = (1..11)
+            .map(|x| (x, 5))
+            .find(|&(x, y)| single.color_for_pixel(&w, x, y) != Color::black())
+            .unwrap();
+
+        let sampled = default_world_camera().with_supersampling(Supersampling::new(
+            64,
+            SamplePattern::Halton,
+            Filter::Tent { radius: 1.0 },
+        ));
+        let edge = sampled.color_for_pixel(&w, x, y);
+        let inside = single.color_for_pixel(&w, x, y);
+
+        assert!(edge.g > 0.0);
+        assert!(edge.g < inside.g);
+    }
+
+    #[test]
+    fn zero_aperture_is_pinhole() {
+        let c = default_world_camera().with_depth_of_field(0.0, 5.0);
+        let pinhole = default_world_camera();
+        let r = c.ray_through_lens(3.2, 7.9, (0.1, 0.8));
+        let expected = pinhole.ray_for_film_point(3.2, 7.9);
+        assert_eq!(r.origin(), expected.origin());
+        assert_eq!(r.direction(), expected.direction());
+    }
+
+    #[test]
+    fn lens_rays_converge_on_focal_plane() {
+        let c = Camera::new(11.0, 11.0, FRAC_PI_2).with_depth_of_field(0.5, 4.0);
+        let pinhole = c.ray_for_film_point(2.5, 8.5);
+        let focus = pinhole.position(4.0 / -pinhole.direction().z());
+
+        for lens in [(0.0, 0.0), (0.9, 0.2), (0.3, 1.0)] {
+            let r = c.ray_through_lens(2.5, 8.5, lens);
+            assert!(r.origin().z().approx_eq(0.0));
+            let t = (focus.z() - r.origin().z()) / r.direction().z();
+            assert_eq!(r.position(t), focus);
+        }
+        assert!(c.ray_through_lens(2.5, 8.5, (0.0, 0.0)).origin() != Point::zero());
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::new(100.0, 50.0, FRAC_PI_2)
+            .with_projection(Projection::Orthographic { width: 10.0 });
+
+        let center = c.ray_for_film_point(50.0, 25.0);
+        assert_eq!(center.origin(), Point::zero());
+        assert_eq!(center.direction(), Vec3::new(0.0, 0.0, -1.0));
+
+        let corner = c.ray_for_film_point(0.0, 0.0);
+        assert_eq!(corner.origin(), Point::new(5.0, 2.5, 0.0));
+        assert_eq!(corner.direction(), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly_to_half_fov_at_edges() {
+        let c = Camera::new(100.0, 100.0, PI).with_projection(Projection::Fisheye);
+
+        assert_eq!(
+            c.ray_for_film_point(50.0, 50.0).direction(),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+        assert_eq!(
+            c.ray_for_film_point(0.0, 50.0).direction(),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            c.ray_for_film_point(50.0, 25.0).direction(),
+            Vec3::new(0.0, SQRT_2 / 2.0, -SQRT_2 / 2.0)
+        );
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let c = Camera::new(200.0, 100.0, FRAC_PI_2).with_projection(Projection::Equirectangular);
+
+        assert_eq!(
+            c.ray_for_film_point(100.0, 50.0).direction(),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+        assert_eq!(
+            c.ray_for_film_point(150.0, 50.0).direction(),
+            Vec3::new(-1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            c.ray_for_film_point(0.0, 50.0).direction(),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            c.ray_for_film_point(100.0, 0.0).direction(),
+            Vec3::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn projections_follow_view_transform() {
+        let mut c =
+            Camera::new(200.0, 100.0, FRAC_PI_2).with_projection(Projection::Equirectangular);
+        c.set_transformation(
+            Transformations::rotate_y(FRAC_PI_4) * Transformations::translate(0.0, -2.0, 5.0),
+        );
+        let r = c.ray_for_film_point(100.0, 50.0);
+        assert_eq!(r.origin(), Point::new(0.0, 2.0, -5.0));
+        assert_eq!(r.direction(), Vec3::new(SQRT_2 / 2.0, 0.0, -SQRT_2 / 2.0));
+    }
+
+    #[test]
+    fn moving_sphere_smears_across_the_shutter() {
+        let mut w = World::new();
+        let mut s = Object::sphere();
+        s.set_motion(
+            Transformations::translate(-1.5, 0.0, 0.0),
+            Transformations::translate(1.5, 0.0, 0.0),
+        )
+        .unwrap();
+        w.push_object(s);
+        w.push_light_source(PointLight::new(
+            Point::new(-10.0, 10.0, -10.0),
+            Color::white(),
+        ));
+        let c = default_world_camera().with_supersampling(Supersampling::new(
+            64,
+            SamplePattern::Jittered,
+            Filter::Box,
+        ));
+
+        // the sphere crosses the center pixel mid-shutter, at time 0 it sits off to the side
+        let blurred = c.color_for_pixel(&w, 5, 5);
+        let frozen = c.with_shutter(0.0, 0.0).color_for_pixel(&w, 5, 5);
+
+        assert!(blurred.r > 0.0);
+        assert_eq!(frozen, Color::black());
+    }
+
+    #[test]
+    fn wavelength_strata_are_a_shuffled_permutation() {
+        let order = shuffled(64, &mut Rng::for_sample(3, 4, usize::MAX, 0));
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..64).collect::<Vec<_>>());
+        assert_ne!(order, sorted);
+    }
 }
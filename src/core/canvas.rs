@@ -1,11 +1,14 @@
 use std::{fs::File, io::Write, path::Path};
 
+use super::tone_mapping::OutputTransform;
 use crate::primitives::color::*;
 
+#[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pub pixels: Vec<Color>,
+    pub output_transform: OutputTransform,
 }
 
 impl Canvas {
@@ -14,16 +17,22 @@ impl Canvas {
             width,
             height,
             pixels: vec![Color::black(); width * height],
+            output_transform: OutputTransform::default(),
         }
     }
 
+    pub fn with_output_transform(mut self, output_transform: OutputTransform) -> Self {
+        self.output_transform = output_transform;
+        self
+    }
+
     pub fn export_to_ppm(&self, path: &'static str) -> Result<(), std::io::Error> {
         let p = Path::new(path);
         let mut file = File::create(p)?;
         let header = format!("P3\n{} {}\n255\n", self.width, self.height);
         file.write_all(header.as_bytes())?;
         for pixel in self.pixels.iter() {
-            let (r, g, b) = clamp_color(pixel);
+            let (r, g, b) = self.output_transform.apply(pixel);
             let line = format!("{} {} {}\n", r, g, b);
             file.write_all(line.as_bytes())?;
         }
@@ -36,19 +45,30 @@ impl Canvas {
             self[y as usize][x as usize] = color;
         }
     }
-}
 
-fn clamp_color(color: &Color) -> (u8, u8, u8) {
-    // let r = f64::max(0.0, f64::min(color.r, 1.0));
-    // let g = f64::max(0.0, f64::min(color.g, 1.0));
-    // let b = f64::max(0.0, f64::min(color.b, 1.0));
-    // return ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8);
+    // unfiltered lookup of the pixel covering (u, v), see `sample`
+    pub fn pixel_at_uv(&self, u: f64, v: f64) -> Color {
+        let x = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v.clamp(0.0, 1.0) * self.height as f64) as usize).min(self.height - 1);
+        self[y][x]
+    }
+
+    // bilinearly filtered lookup, (u, v) in [0, 1]^2 spans the image from its top-left corner,
+    // u wraps around horizontally and v is clamped
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = u.rem_euclid(1.0) * self.width as f64 - 0.5;
+        let y = (v.clamp(0.0, 1.0) * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
 
-    let r = (color.r * 255.0).clamp(0.0, 255.0).round();
-    let g = (color.g * 255.0).clamp(0.0, 255.0).round();
-    let b = (color.b * 255.0).clamp(0.0, 255.0).round();
+        let column = |x: f64| (x as isize).rem_euclid(self.width as isize) as usize;
+        let (c0, c1) = (column(x0), column(x0 + 1.0));
+        let (r0, r1) = (y0 as usize, usize::min(y0 as usize + 1, self.height - 1));
 
-    return (r as u8, g as u8, b as u8);
+        let top = self[r0][c0] * (1.0 - tx) + self[r0][c1] * tx;
+        let bottom = self[r1][c0] * (1.0 - tx) + self[r1][c1] * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
 }
 
 impl std::ops::Index<usize> for Canvas {
@@ -89,4 +109,39 @@ mod tests {
         c[2][3] = Color::black();
         assert_eq!(c[2][3], Color::black());
     }
+
+    #[test]
+    fn sampling_at_pixel_centers_returns_pixels() {
+        let mut c = Canvas::new(2, 2);
+        c[0][0] = Color::red();
+        c[0][1] = Color::green();
+        c[1][0] = Color::blue();
+        c[1][1] = Color::white();
+
+        assert_eq!(c.sample(0.25, 0.25), Color::red());
+        assert_eq!(c.sample(0.75, 0.25), Color::green());
+        assert_eq!(c.sample(0.25, 0.75), Color::blue());
+        assert_eq!(c.sample(0.5, 0.25), Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn pixel_lookup_by_uv_is_unfiltered() {
+        let mut c = Canvas::new(2, 1);
+        c[0][0] = Color::red();
+        c[0][1] = Color::blue();
+
+        assert_eq!(c.pixel_at_uv(0.49, 0.5), Color::red());
+        assert_eq!(c.pixel_at_uv(0.5, 1.0), Color::blue());
+        assert_eq!(c.pixel_at_uv(-0.25, 0.0), Color::blue());
+    }
+
+    #[test]
+    fn sampling_wraps_horizontally() {
+        let mut c = Canvas::new(2, 1);
+        c[0][0] = Color::red();
+        c[0][1] = Color::blue();
+
+        assert_eq!(c.sample(0.0, 0.5), Color::new(0.5, 0.0, 0.5));
+        assert_eq!(c.sample(1.25, 0.5), Color::red());
+    }
 }
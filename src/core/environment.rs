@@ -0,0 +1,206 @@
+use std::f64::consts::PI;
+
+use super::{
+    background::{equirectangular_direction, equirectangular_uv},
+    canvas::Canvas,
+    light::Material,
+    sampling::{random_2d, Distribution2D},
+};
+use crate::primitives::{color::Color, vec3::Vec3};
+
+// direction picked on the environment, `pdf` is with respect to solid angle
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentSample {
+    pub direction: Vec3,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+// light arriving from an equirectangular HDR image surrounding the scene, importance sampled
+// in proportion to the luminance of its pixels
+#[derive(Clone)]
+pub struct EnvironmentLight {
+    image: Canvas,
+    distribution: Distribution2D,
+    // shadow rays traced per shading point
+    samples: usize,
+}
+
+impl EnvironmentLight {
+    pub fn new(image: Canvas, samples: usize) -> Self {
+        let (width, height) = (image.width, image.height);
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // rows near the poles cover less of the sphere
+            let sin_theta = f64::sin(PI * (y as f64 + 0.5) / height as f64);
+            for x in 0..width {
+                weights.push(image[y][x].luminance().max(0.0) * sin_theta);
+            }
+        }
+
+        EnvironmentLight {
+            distribution: Distribution2D::new(&weights, width, height),
+            image,
+            samples: samples.max(1),
+        }
+    }
+
+    pub fn image(&self) -> &Canvas {
+        &self.image
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    // unfiltered, so that radiance is constant wherever the sampling density is
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        if self.image.width == 0 || self.image.height == 0 {
+            return Color::black();
+        }
+        let (u, v) = equirectangular_uv(direction.normalize());
+        self.image.pixel_at_uv(u, v)
+    }
+
+    pub fn sample(&self, u0: f64, u1: f64) -> EnvironmentSample {
+        let ((u, v), pdf_uv) = self.distribution.sample(u0, u1);
+        let sin_theta = f64::sin(PI * v);
+        let direction = equirectangular_direction(u, v);
+
+        // (u, v) covers 2pi radians of longitude and pi radians of latitude
+        let pdf = if sin_theta == 0.0 {
+            0.0
+        } else {
+            pdf_uv / (2.0 * PI * PI * sin_theta)
+        };
+
+        EnvironmentSample {
+            direction,
+            radiance: self.radiance(direction),
+            pdf,
+        }
+    }
+
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = equirectangular_uv(direction.normalize());
+        let sin_theta = f64::sin(PI * v);
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // Monte Carlo estimate of the light reflected toward `eyev` by `Material::brdf`, `visible`
+    // gives the fraction of the light arriving from a direction, black when it is blocked
+    pub fn shade(
+        &self,
+        m: &Material,
+        normalv: Vec3,
+        eyev: Vec3,
+        seed: u32,
+        visible: impl Fn(Vec3) -> Color,
+    ) -> Color {
+        let mut total = Color::black();
+        for i in 0..self.samples {
+            let (u0, u1) = random_2d(seed, i, 0);
+            let s = self.sample(u0, u1);
+            let cos = s.direction ^ normalv;
+            if s.pdf == 0.0 || cos <= 0.0 {
+                continue;
+            }
+            total += s.radiance
+                * visible(s.direction)
+                * m.brdf(normalv, eyev, s.direction)
+                * (cos / s.pdf);
+        }
+        total * (1.0 / self.samples as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::sampling::random_1d,
+        primitives::{float::ApproxEq, tuple::Tuple},
+    };
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        c.pixels = vec![color; width * height];
+        c
+    }
+
+    #[test]
+    fn samples_concentrate_on_bright_pixels() {
+        let mut image = filled(8, 4, Color::new(0.01, 0.01, 0.01));
+        image[1][6] = Color::new(100.0, 100.0, 100.0);
+        let env = EnvironmentLight::new(image, 1);
+
+        let bright = (0..100)
+            .map(|i| env.sample(random_1d(9, i, 0), random_1d(9, i, 1)))
+            .filter(|s| s.radiance.r > 50.0)
+            .count();
+        assert!(bright > 90);
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf_lookup() {
+        let mut image = filled(8, 4, Color::new(0.5, 0.5, 0.5));
+        image[2][3] = Color::new(5.0, 2.0, 1.0);
+        let env = EnvironmentLight::new(image, 1);
+
+        for i in 0..20 {
+            let s = env.sample(random_1d(4, i, 0), random_1d(4, i, 1));
+            assert!((s.pdf - env.pdf(s.direction)).abs() < 1e-6 * s.pdf);
+        }
+    }
+
+    #[test]
+    fn uniform_environment_pdf_is_uniform_over_the_sphere() {
+        let env = EnvironmentLight::new(filled(16, 8, Color::white()), 1);
+        let pdf = env.pdf(Vec3::new(0.3, 0.2, -1.0));
+
+        // rows are weighted by their midpoint, so allow for the discretization
+        assert!((pdf - 1.0 / (4.0 * PI)).abs() < 0.01);
+    }
+
+    #[test]
+    fn white_environment_lights_a_diffuse_surface_like_a_head_on_light() {
+        let env = EnvironmentLight::new(filled(32, 16, Color::white()), 512);
+        let m = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let normalv = Vec3::new(0.0, 1.0, 0.0);
+
+        let c = env.shade(&m, normalv, normalv, 7, |_| Color::white());
+
+        assert!(c.r.approx_eq_epsilon(0.9, 0.1));
+    }
+
+    #[test]
+    fn empty_environment_gives_no_light() {
+        let env = EnvironmentLight::new(Canvas::new(0, 4), 4);
+        let normalv = Vec3::new(0.0, 1.0, 0.0);
+
+        assert_eq!(env.sample(0.5, 0.5).radiance, Color::black());
+        assert_eq!(
+            env.shade(&Material::default(), normalv, normalv, 1, |_| Color::white(
+            )),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn fully_occluded_environment_gives_no_light() {
+        let env = EnvironmentLight::new(filled(8, 4, Color::white()), 16);
+        let normalv = Vec3::new(0.0, 1.0, 0.0);
+
+        let c = env.shade(&Material::default(), normalv, normalv, 1, |_| {
+            Color::black()
+        });
+
+        assert_eq!(c, Color::black());
+    }
+}
@@ -0,0 +1,895 @@
+use std::f64::consts::PI;
+
+use super::{
+    light::Material,
+    object::IntersectionState,
+    random::Rng,
+    ray::Ray,
+    sampling::{cosine_hemisphere, hash},
+    shading::ShadingModel,
+    world::{World, MAX_RECURSION_DEPTH},
+};
+use crate::primitives::{color::Color, float::SURFACE_EPSILON, tuple::Tuple, vec3::Vec3};
+
+// lighting algorithm evaluated by the camera for every camera ray, `World` only describes
+// the scene. `rng` belongs to the camera sample, integrators that make random decisions draw
+// from it so renders are reproducible. Integrators are shared between render threads.
+pub trait Integrator: Sync {
+    fn color_at(&self, world: &World, ray: Ray, rng: &mut Rng) -> Color;
+}
+
+// the book's recursive Whitted ray tracer: Phong direct lighting, shadows, perfect mirror
+// reflection and refraction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Whitted {
+    max_depth: usize,
+}
+
+impl Default for Whitted {
+    fn default() -> Self {
+        Whitted {
+            max_depth: MAX_RECURSION_DEPTH,
+        }
+    }
+}
+
+impl Whitted {
+    pub fn new(max_depth: usize) -> Self {
+        Whitted { max_depth }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+impl Integrator for Whitted {
+    fn color_at(&self, world: &World, ray: Ray, rng: &mut Rng) -> Color {
+        world.color_at_with_depth(ray.with_seed(rng.next_u32()), self.max_depth)
+    }
+}
+
+// unbiased Monte Carlo path tracer. Surfaces scatter with `Material::brdf`, importance sampled
+// by `Material::sample_direction`; `reflective` and `transparency` take their share of the
+// light as perfect mirror and refraction lobes instead of adding to it, and `ambient` is
+// ignored since indirect light is computed. Light sources
+// are sampled at every vertex (next-event estimation); emissive objects and the environment
+// light can also be hit by chance, both strategies are combined with the power heuristic.
+// Rays leaving the scene see the background, or the environment light when there is one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathTracer {
+    max_depth: usize,
+    // bounces before paths start being terminated at random
+    roulette_depth: usize,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer {
+            max_depth: 16,
+            roulette_depth: 3,
+        }
+    }
+}
+
+// which lobe of the material a path continues through
+enum Lobe {
+    Surface,
+    Mirror,
+    Refraction,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: usize) -> Self {
+        PathTracer {
+            max_depth,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_roulette_depth(mut self, roulette_depth: usize) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn roulette_depth(&self) -> usize {
+        self.roulette_depth
+    }
+
+    // light arriving at the shading point from every light source, emitter and the environment
+    fn direct_lighting(
+        &self,
+        world: &World,
+        state: &IntersectionState,
+        m: &Material,
+        rng: &mut Rng,
+    ) -> Color {
+        let (point, normalv, eyev) = (state.over_point, state.normalv, state.eyev);
+        // fraction of the light that the shadow ray lets through
+        let visible = |direction: Vec3, distance: f64| {
+            let ray = Ray::new(point, direction).with_time(state.time);
+            world.shadow_ray_visibility(&ray, distance)
+        };
+        let mut total = Color::black();
+
+        // point-like lights only exist through this estimator, scaled by pi to match the
+        // normalization of `Material::brdf`
+        for light in world.light_sources() {
+            let rays = light.shadow_rays(point, rng.next_u32());
+            let pick = rng.next_f64() * rays.len() as f64;
+            let (direction, distance) = rays[(pick as usize).min(rays.len() - 1)];
+            let cos = direction ^ normalv;
+            if cos <= 0.0 {
+                continue;
+            }
+            let intensity = light.sample(point).intensity
+                * light.attenuation().factor(distance)
+                * visible(direction, distance);
+            total += intensity * m.brdf(normalv, eyev, direction) * (PI * cos);
+        }
+
+        for emitter in world.emitters() {
+            if std::ptr::eq(emitter, state.object) {
+                continue;
+            }
+            let (u, v) = rng.next_2d();
+            let sample = emitter.sample_surface(u, v, state.time);
+            let to_light = sample.point - point;
+            let distance = to_light.magnitude();
+            let direction = to_light.normalize();
+            let cos_light = -direction ^ sample.normal;
+            let cos = direction ^ normalv;
+            if cos_light <= 0.0 || cos <= 0.0 {
+                continue;
+            }
+            let light_pdf = sample.pdf * distance * distance / cos_light;
+            let weight = power_heuristic(light_pdf, m.pdf(normalv, eyev, direction));
+            total += emitter.material().emitted()
+                * visible(direction, distance - SURFACE_EPSILON)
+                * m.brdf(normalv, eyev, direction)
+                * (cos * weight / light_pdf);
+        }
+
+        if let Some(environment) = world.environment_light() {
+            let (u, v) = rng.next_2d();
+            let sample = environment.sample(u, v);
+            let cos = sample.direction ^ normalv;
+            if sample.pdf > 0.0 && cos > 0.0 {
+                let weight = power_heuristic(sample.pdf, m.pdf(normalv, eyev, sample.direction));
+                total += sample.radiance
+                    * visible(sample.direction, f64::INFINITY)
+                    * m.brdf(normalv, eyev, sample.direction)
+                    * (cos * weight / sample.pdf);
+            }
+        }
+
+        total
+    }
+
+    // radiance of a ray that left the scene, `bsdf_pdf` is None after a perfectly specular
+    // bounce and for camera rays
+    fn escaped(&self, world: &World, ray: &Ray, depth: usize, bsdf_pdf: Option<f64>) -> Color {
+        match world.environment_light() {
+            Some(environment) if depth > 0 => {
+                let radiance = environment.radiance(ray.direction());
+                match bsdf_pdf {
+                    None => radiance,
+                    Some(pdf) => radiance * power_heuristic(pdf, environment.pdf(ray.direction())),
+                }
+            }
+            _ => world.background().color(ray.direction()),
+        }
+    }
+
+    fn choose_lobe(m: &Material, state: &IntersectionState, u: f64) -> Option<Lobe> {
+        let (mut mirror, mut refraction) = (m.reflective, m.transparency);
+        if mirror > 0.0 && refraction > 0.0 {
+            let reflectance = state.schlick();
+            mirror *= reflectance;
+            refraction *= 1.0 - reflectance;
+        }
+        let specular = mirror + refraction;
+        if specular > 1.0 {
+            mirror /= specular;
+            refraction /= specular;
+        }
+        let surface = if m.diffuse > 0.0 || m.specular > 0.0 {
+            1.0 - mirror - refraction
+        } else {
+            0.0
+        };
+
+        let total = surface + mirror + refraction;
+        if total <= 0.0 {
+            return None;
+        }
+        // lobes are picked with probability equal to their weight, so the weights cancel
+        // out of the throughput; black materials absorb what is left
+        let u = u * total.max(1.0);
+        if u < surface {
+            Some(Lobe::Surface)
+        } else if u < surface + mirror {
+            Some(Lobe::Mirror)
+        } else if u < total {
+            Some(Lobe::Refraction)
+        } else {
+            None
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn color_at(&self, world: &World, ray: Ray, rng: &mut Rng) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = ray;
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for depth in 0..=self.max_depth {
+            let xs = world.intersect_world(&ray);
+            let end = xs.hit().map_or(f64::INFINITY, |hit| hit.t);
+            let (through_media, inscattered) =
+                world.media_along(&ray.with_seed(rng.next_u32()), end);
+            radiance += throughput * inscattered;
+            throughput *= through_media;
+
+            let hit = match xs.hit() {
+                Some(hit) => *hit,
+                None => {
+                    radiance += throughput * self.escaped(world, &ray, depth, bsdf_pdf);
+                    break;
+                }
+            };
+            let state = IntersectionState::prepare(hit, ray, &xs);
+            let m = state.object.material();
+            throughput *= state.transmittance;
+
+            if m.is_emissive() {
+                // weighted against the chance of next-event estimation picking the same point
+                let weight = match bsdf_pdf {
+                    Some(pdf) if !state.inside => {
+                        let cos_light = state.normalv ^ state.eyev;
+                        let light_pdf =
+                            state.object.surface_pdf(state.point, state.time) * hit.t * hit.t
+                                / cos_light;
+                        power_heuristic(pdf, light_pdf)
+                    }
+                    _ => 1.0,
+                };
+                radiance += throughput * m.emitted() * weight;
+            }
+            if depth == self.max_depth {
+                break;
+            }
+
+            let lobe = match Self::choose_lobe(&m, &state, rng.next_f64()) {
+                Some(lobe) => lobe,
+                None => break,
+            };
+            let (origin, direction) = match lobe {
+                Lobe::Surface => {
+                    // subsurface materials take their diffuse part beneath the surface and keep
+                    // the highlight on top, each picked in proportion to its weight
+                    let walk = m
+                        .subsurface_scattering()
+                        .map(|subsurface| (subsurface, m.diffuse / (m.diffuse + m.specular)));
+                    match walk {
+                        Some((subsurface, p)) if rng.next_f64() < p => {
+                            let Some(exit) = subsurface.walk(
+                                state.object,
+                                m.color,
+                                state.point,
+                                state.normalv,
+                                state.time,
+                                rng,
+                            ) else {
+                                break;
+                            };
+                            // light leaves the exit point as from a white lambertian surface
+                            let exit_state = IntersectionState {
+                                point: exit.point,
+                                over_point: exit.point + exit.normal * SURFACE_EPSILON,
+                                normalv: exit.normal,
+                                eyev: exit.normal,
+                                ..state
+                            };
+                            let exit_material = Material {
+                                color: Color::white(),
+                                diffuse: 1.0,
+                                specular: 0.0,
+                                model: ShadingModel::default(),
+                                ..m
+                            };
+                            throughput *= exit.weight * (m.diffuse / p);
+                            radiance += throughput
+                                * self.direct_lighting(world, &exit_state, &exit_material, rng);
+                            let (u, v) = rng.next_2d();
+                            let direction = cosine_hemisphere(exit.normal, u, v);
+                            bsdf_pdf = Some(exit_material.pdf(exit.normal, exit.normal, direction));
+                            (exit_state.over_point, direction)
+                        }
+                        _ => {
+                            if let Some((_, p)) = walk {
+                                throughput *= 1.0 / (1.0 - p);
+                            }
+                            let shell = m.shell();
+                            radiance += throughput
+                                * (self.direct_lighting(world, &state, &shell, rng)
+                                    + world.caustic_color(&state));
+                            let direction = shell.sample_direction(state.normalv, state.eyev, rng);
+                            let pdf = shell.pdf(state.normalv, state.eyev, direction);
+                            let cos = direction ^ state.normalv;
+                            if pdf <= 0.0 || cos <= 0.0 {
+                                break;
+                            }
+                            throughput = throughput
+                                * shell.brdf(state.normalv, state.eyev, direction)
+                                * (cos / pdf);
+                            bsdf_pdf = Some(pdf);
+                            (state.over_point, direction)
+                        }
+                    }
+                }
+                Lobe::Mirror => {
+                    bsdf_pdf = None;
+                    let (u, v) = rng.next_2d();
+                    (state.over_point, state.glossy_reflection(u, v))
+                }
+                Lobe::Refraction => {
+                    bsdf_pdf = None;
+                    let (u, v) = rng.next_2d();
+                    match state.glossy_refraction(u, v) {
+                        Some(direction) => (state.under_point, direction),
+                        // total internal reflection
+                        None => (state.over_point, state.reflectv),
+                    }
+                }
+            };
+
+            if depth >= self.roulette_depth {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if survival <= 0.0 || rng.next_f64() >= survival {
+                    break;
+                }
+                throughput *= 1.0 / survival;
+            }
+            ray = state.spawn_ray(origin, direction);
+        }
+
+        radiance
+    }
+}
+
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+// clay render: the fraction of cosine-weighted hemisphere rays from each hit that travel
+// `max_distance` without hitting anything, optionally tinted by the material color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    samples: usize,
+    max_distance: f64,
+    material_color: bool,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, max_distance: f64) -> Self {
+        AmbientOcclusion {
+            samples: samples.max(1),
+            max_distance,
+            material_color: false,
+        }
+    }
+
+    pub fn with_material_color(mut self, material_color: bool) -> Self {
+        self.material_color = material_color;
+        self
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn color_at(&self, world: &World, ray: Ray, rng: &mut Rng) -> Color {
+        let xs = world.intersect_world(&ray);
+        let hit = match xs.hit() {
+            Some(hit) => *hit,
+            None => return world.background().color(ray.direction()),
+        };
+        let state = IntersectionState::prepare(hit, ray, &xs);
+
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let (u, v) = rng.next_2d();
+                let direction = cosine_hemisphere(state.normalv, u, v);
+                let ray = Ray::new(state.over_point, direction).with_time(state.time);
+                !world.is_occluded(&ray, self.max_distance)
+            })
+            .count();
+        let fraction = unoccluded as f64 / self.samples as f64;
+
+        if self.material_color {
+            state.object.material().color * fraction
+        } else {
+            Color::new(fraction, fraction, fraction)
+        }
+    }
+}
+
+// false-color views of the scene for inspecting geometry, rays that miss are black
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    // surface normal facing the eye, components remapped from [-1, 1] to [0, 1]
+    Normals,
+    // hit distance, white at the camera fading to black at `far`
+    Depth { far: f64 },
+    // texture coordinates as red and green
+    Uv,
+    // a distinct color per object in the world
+    ObjectId,
+    // ray-object tests spent on the camera ray and on the shadow, reflected and refracted rays
+    // `Whitted` traces below it, blue when cheap to red at `max` tests
+    IntersectionTests { max: usize },
+}
+
+impl Integrator for DebugView {
+    fn color_at(&self, world: &World, ray: Ray, _rng: &mut Rng) -> Color {
+        let mut tests = 0;
+        let xs = world.intersect_world_counting(&ray, &mut tests);
+        let hit = xs.hit().copied();
+        let state = hit.map(|hit| IntersectionState::prepare(hit, ray, &xs));
+
+        match (self, state) {
+            (DebugView::IntersectionTests { max }, state) => {
+                if let Some(state) = state {
+                    tests += world.intersection_tests(&state, MAX_RECURSION_DEPTH, true);
+                }
+                heat(tests as f64 / (*max).max(1) as f64)
+            }
+            (_, None) => Color::black(),
+            (DebugView::Normals, Some(state)) => {
+                let n = state.normalv;
+                Color::new(n.x() + 1.0, n.y() + 1.0, n.z() + 1.0) * 0.5
+            }
+            (DebugView::Depth { far }, Some(state)) => {
+                let depth = 1.0 - (state.t * ray.direction().magnitude() / far).clamp(0.0, 1.0);
+                Color::new(depth, depth, depth)
+            }
+            (DebugView::Uv, Some(state)) => {
+                let (u, v) = state.object.uv_at(state.point, state.time);
+                Color::new(u, v, 0.0)
+            }
+            (DebugView::ObjectId, Some(state)) => {
+                let index = world
+                    .objects()
+                    .iter()
+                    .position(|o| std::ptr::eq(o, state.object))
+                    .unwrap_or(0);
+                let h = hash(index as u32);
+                let channel = |shift: u32| ((h >> shift) & 0xff) as f64 / 255.0;
+                Color::new(channel(0), channel(8), channel(16))
+            }
+        }
+    }
+}
+
+// blue -> green -> red ramp over [0, 1], saturating above
+fn heat(x: f64) -> Color {
+    let x = x.clamp(0.0, 1.0);
+    if x < 0.5 {
+        Color::new(0.0, 2.0 * x, 1.0 - 2.0 * x)
+    } else {
+        Color::new(2.0 * x - 1.0, 2.0 - 2.0 * x, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            background::Background,
+            light::Light,
+            medium::{Medium, Volume},
+            object::Object,
+            shading::ShadingModel,
+            subsurface::Subsurface,
+            transforms::Transformations,
+        },
+        primitives::{float::ApproxEq, point::Point},
+    };
+
+    fn average(integrator: &dyn Integrator, world: &World, ray: Ray, samples: u32) -> Color {
+        let mut sum = Color::black();
+        for sample in 0..samples {
+            sum += integrator.color_at(world, ray, &mut Rng::new(0, sample as u64));
+        }
+        sum * (1.0 / samples as f64)
+    }
+
+    fn matte(color: Color) -> Material {
+        Material {
+            color,
+            ambient: 0.0,
+            diffuse: 1.0,
+            specular: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn whitted_matches_world_color_at() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            Whitted::default().color_at(&w, r, &mut Rng::new(0, 0)),
+            w.color_at(r)
+        );
+    }
+
+    #[test]
+    fn whitted_depth_limits_reflections() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::white()));
+        w.push_object(Object::sphere().with_material(Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            reflective: 1.0,
+            ..Default::default()
+        }));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            Whitted::new(0).color_at(&w, r, &mut Rng::new(0, 0)),
+            Color::black()
+        );
+        assert_eq!(
+            Whitted::new(1).color_at(&w, r, &mut Rng::new(0, 0)),
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn path_tracer_direct_light_matches_whitted() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(matte(Color::new(0.8, 0.6, 0.4))));
+        w.push_light_source(Light::point(Point::new(-10.0, 10.0, -10.0), Color::white()));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        // nothing else in the scene to bounce off, so one sample is exact
+        assert_eq!(
+            PathTracer::default().color_at(&w, r, &mut Rng::new(7, 0)),
+            Whitted::default().color_at(&w, r, &mut Rng::new(7, 0))
+        );
+    }
+
+    #[test]
+    fn path_tracer_sees_emission_directly() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(Material {
+            emission: Color::new(2.0, 1.0, 0.5),
+            ..matte(Color::black())
+        }));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            PathTracer::default().color_at(&w, r, &mut Rng::new(0, 0)),
+            Color::new(2.0, 1.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn white_furnace_reflects_the_albedo() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::white()));
+        w.push_object(Object::sphere().with_material(matte(Color::new(0.5, 0.5, 0.5))));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        let c = average(&PathTracer::default(), &w, r, 256);
+        assert!(c.r.approx_eq_epsilon(0.5, 1e-6));
+    }
+
+    #[test]
+    fn microfacet_furnace_keeps_energy_below_one() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::white()));
+        w.push_object(Object::sphere().with_material(Material {
+            model: ShadingModel::Microfacet {
+                metallic: 1.0,
+                roughness: 0.3,
+            },
+            ..matte(Color::white())
+        }));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        let c = average(&PathTracer::default(), &w, r, 512);
+        assert!(c.r > 0.9 && c.r < 1.02);
+    }
+
+    #[test]
+    fn mirror_paths_see_the_background() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::new(0.2, 0.4, 0.6)));
+        w.push_object(Object::sphere().with_material(Material {
+            reflective: 1.0,
+            ..matte(Color::white())
+        }));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            PathTracer::default().color_at(&w, r, &mut Rng::new(3, 0)),
+            Color::new(0.2, 0.4, 0.6)
+        );
+    }
+
+    #[test]
+    fn emitter_lighting_converges_to_the_analytic_irradiance() {
+        // a sphere of radiance L subtending sin(alpha) = r / d lights a facing diffuse surface
+        // with radiance albedo * L * sin^2(alpha), here 1 * 64 * (0.5 / 4)^2 = 1
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(matte(Color::white())));
+        let mut lamp = Object::sphere().with_material(Material {
+            emission: Color::new(64.0, 64.0, 64.0),
+            ..matte(Color::black())
+        });
+        lamp.set_transformation(
+            Transformations::translate(0.0, 5.0, 0.0) * Transformations::scale(0.5, 0.5, 0.5),
+        );
+        w.push_object(lamp);
+        let r = Ray::new(
+            Point::new(2.0, 3.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0).normalize(),
+        );
+
+        let c = average(&PathTracer::new(1), &w, r, 2000);
+        assert!(c.r.approx_eq_epsilon(1.0, 0.05));
+    }
+
+    #[test]
+    fn zero_depth_only_sees_emission() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            PathTracer::new(0).color_at(&w, r, &mut Rng::new(1, 0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn path_tracer_marches_through_fog() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 2.0, 0.0), Color::white()));
+        w.set_fog(Medium::new(Color::new(0.05, 0.05, 0.05), Color::black()));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        // both draw the seed of the march first
+        let c = PathTracer::default().color_at(&w, r, &mut Rng::new(0, 0));
+        assert!(c.r > 0.0);
+        assert_eq!(c, Whitted::default().color_at(&w, r, &mut Rng::new(0, 0)));
+    }
+
+    #[test]
+    fn volumes_shadow_the_path_traced_surface() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        let mut ground = Object::sphere().with_material(matte(Color::white()));
+        ground.set_transformation(
+            Transformations::translate(0.0, -101.0, 0.0)
+                * Transformations::scale(100.0, 100.0, 100.0),
+        );
+        w.push_object(ground);
+        let r = Ray::new(
+            Point::new(4.0, -0.5, 0.0),
+            Vec3::new(-4.0, -0.5, 0.0).normalize(),
+        );
+        let lit = PathTracer::new(1).color_at(&w, r, &mut Rng::new(0, 0));
+
+        let mut smoke = Object::sphere();
+        smoke.set_transformation(Transformations::translate(0.0, 5.0, 0.0));
+        w.push_volume(Volume::new(
+            smoke,
+            Medium::new(Color::black(), Color::new(1.0, 1.0, 1.0)),
+        ));
+        let shadowed = PathTracer::new(1).color_at(&w, r, &mut Rng::new(0, 0));
+
+        assert!(shadowed.r.approx_eq(lit.r * f64::exp(-2.0)));
+        assert!(shadowed
+            .r
+            .approx_eq(Whitted::default().color_at(&w, r, &mut Rng::new(0, 0)).r));
+    }
+
+    #[test]
+    fn path_tracer_carries_light_beneath_the_surface() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        w.push_object(Object::sphere().with_material(Material {
+            subsurface: Some(Subsurface::new(Color::new(1.0, 1.0, 1.0))),
+            ..matte(Color::new(0.9, 0.6, 0.4))
+        }));
+        // the bottom of the sphere only sees the light through the object
+        let r = Ray::new(Point::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let c = average(&PathTracer::default(), &w, r, 256);
+        assert!(c.r > 0.0 && c.r > c.b);
+    }
+
+    #[test]
+    fn path_tracer_keeps_the_highlight_of_subsurface_materials() {
+        let glossy = Material {
+            diffuse: 0.0,
+            specular: 0.9,
+            ..matte(Color::new(0.9, 0.6, 0.4))
+        };
+        let highlight = |m: Material| {
+            let mut w = World::new();
+            w.push_light_source(Light::point(Point::new(0.0, 0.0, -10.0), Color::white()));
+            w.push_object(Object::sphere().with_material(m));
+            let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+            PathTracer::new(1).color_at(&w, r, &mut Rng::new(0, 0))
+        };
+
+        let c = highlight(Material {
+            subsurface: Some(Subsurface::new(Color::new(1.0, 1.0, 1.0))),
+            ..glossy
+        });
+        assert!(c.r > 0.0);
+        assert_eq!(c, highlight(glossy));
+    }
+
+    fn sphere_on_ground() -> World {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(matte(Color::new(1.0, 0.5, 0.25))));
+        let mut ground = Object::sphere();
+        ground.set_transformation(
+            Transformations::translate(0.0, -101.0, 0.0)
+                * Transformations::scale(100.0, 100.0, 100.0),
+        );
+        w.push_object(ground);
+        w
+    }
+
+    #[test]
+    fn open_surfaces_are_unoccluded() {
+        let w = sphere_on_ground();
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let ao = AmbientOcclusion::new(32, f64::INFINITY);
+        assert_eq!(ao.color_at(&w, r, &mut Rng::new(0, 0)), Color::white());
+    }
+
+    #[test]
+    fn creases_are_occluded_within_max_distance() {
+        let w = sphere_on_ground();
+        // looking at the ground right next to where the sphere touches it
+        let r = Ray::new(Point::new(1.2, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let near = AmbientOcclusion::new(64, f64::INFINITY).color_at(&w, r, &mut Rng::new(0, 0));
+        assert!(near.r > 0.0 && near.r < 0.9);
+
+        let short = AmbientOcclusion::new(64, 0.01).color_at(&w, r, &mut Rng::new(0, 0));
+        assert_eq!(short, Color::white());
+    }
+
+    #[test]
+    fn ambient_occlusion_can_use_the_material_color() {
+        let w = sphere_on_ground();
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let ao = AmbientOcclusion::new(8, f64::INFINITY).with_material_color(true);
+        assert_eq!(
+            ao.color_at(&w, r, &mut Rng::new(0, 0)),
+            Color::new(1.0, 0.5, 0.25)
+        );
+    }
+
+    #[test]
+    fn ambient_occlusion_misses_see_the_background() {
+        let mut w = sphere_on_ground();
+        w.set_background(Background::Solid(Color::new(0.1, 0.2, 0.3)));
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let ao = AmbientOcclusion::new(8, 1.0);
+        assert_eq!(
+            ao.color_at(&w, r, &mut Rng::new(0, 0)),
+            Color::new(0.1, 0.2, 0.3)
+        );
+    }
+
+    #[test]
+    fn debug_normals_remap_to_colors() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let c = DebugView::Normals.color_at(&w, r, &mut Rng::new(0, 0));
+        assert_eq!(c, Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn debug_depth_fades_to_far() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let c = DebugView::Depth { far: 8.0 }.color_at(&w, r, &mut Rng::new(0, 0));
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+
+        let miss = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+        let c = DebugView::Depth { far: 8.0 }.color_at(&w, miss, &mut Rng::new(0, 0));
+        assert_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn debug_uv_shows_texture_coordinates() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let c = DebugView::Uv.color_at(&w, r, &mut Rng::new(0, 0));
+        assert_eq!(c, Color::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn debug_object_ids_differ_between_objects() {
+        let w = sphere_on_ground();
+        let on_sphere = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let on_ground = Ray::new(Point::new(3.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let a = DebugView::ObjectId.color_at(&w, on_sphere, &mut Rng::new(0, 0));
+        let b = DebugView::ObjectId.color_at(&w, on_ground, &mut Rng::new(0, 0));
+        assert_ne!(a, b);
+        assert_eq!(
+            a,
+            DebugView::ObjectId.color_at(&w, on_sphere, &mut Rng::new(1, 0))
+        );
+    }
+
+    #[test]
+    fn debug_heat_map_counts_intersection_tests() {
+        let mut w = sphere_on_ground();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        let miss = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        // brute force tests every object, hit or not
+        let view = DebugView::IntersectionTests { max: 2 };
+        assert_eq!(view.color_at(&w, miss, &mut Rng::new(0, 0)), Color::red());
+        let view = DebugView::IntersectionTests { max: 4 };
+        assert_eq!(
+            view.color_at(&w, miss, &mut Rng::new(0, 0)),
+            Color::new(0.0, 1.0, 0.0)
+        );
+
+        // a hit also pays for the shadow ray towards the light
+        let hit = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let view = DebugView::IntersectionTests { max: 4 };
+        assert_eq!(view.color_at(&w, hit, &mut Rng::new(0, 0)), Color::red());
+        let view = DebugView::IntersectionTests { max: 8 };
+        assert_eq!(
+            view.color_at(&w, hit, &mut Rng::new(0, 0)),
+            Color::new(0.0, 1.0, 0.0)
+        );
+
+        // and a mirror for the reflected ray, which goes back up and misses
+        let mut mirrored = World::new();
+        mirrored.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        for (i, o) in w.objects().iter().enumerate() {
+            let mut o = *o;
+            if i == 0 {
+                o.set_material(Material {
+                    reflective: 1.0,
+                    ..o.material()
+                });
+            }
+            mirrored.push_object(o);
+        }
+        let view = DebugView::IntersectionTests { max: 6 };
+        assert_eq!(
+            view.color_at(&mirrored, hit, &mut Rng::new(0, 0)),
+            Color::red()
+        );
+    }
+}
@@ -1,9 +1,70 @@
+use std::f64::consts::PI;
+
+use super::{
+    random::Rng,
+    sampling::{concentric_disk, cosine_hemisphere, orthonormal_basis, random_2d},
+    shading::{
+        blinn_phong, microfacet_brdf, microfacet_pdf, oren_nayar, sample_ggx_normal,
+        sample_microfacet, DiffuseModel, ShadingModel, SpecularModel,
+    },
+    spectrum::Dispersion,
+    subsurface::Subsurface,
+};
 use crate::primitives::{color::Color, point::Point, vec3::Vec3};
 
+// closest a point is taken to be to a light when computing the falloff
+const MIN_DISTANCE: f64 = 0.01;
+
+// intensity falloff with distance, 1 / (constant + linear * d + quadratic * d^2)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Default for Attenuation {
+    // constant intensity at every distance
+    fn default() -> Self {
+        Attenuation::new(1.0, 0.0, 0.0)
+    }
+}
+
+impl Attenuation {
+    pub fn new(constant: f64, linear: f64, quadratic: f64) -> Self {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    // physically based falloff, intensity is then radiant intensity at unit distance
+    pub fn inverse_square() -> Self {
+        Attenuation::new(0.0, 0.0, 1.0)
+    }
+
+    pub fn factor(&self, distance: f64) -> f64 {
+        if distance.is_infinite() {
+            return if self.linear == 0.0 && self.quadratic == 0.0 {
+                1.0 / self.constant
+            } else {
+                0.0
+            };
+        }
+        // a surface touching the light would otherwise receive unbounded intensity
+        let distance = distance.max(MIN_DISTANCE);
+        let denominator =
+            self.constant + self.linear * distance + self.quadratic * distance * distance;
+        1.0 / denominator.max(f64::EPSILON)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct PointLight {
     position: Point,
     intensity: Color,
+    attenuation: Attenuation,
 }
 
 impl PointLight {
@@ -11,9 +72,15 @@ impl PointLight {
         return PointLight {
             position,
             intensity,
+            attenuation: Attenuation::default(),
         };
     }
 
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
     pub fn intensity(&self) -> Color {
         return self.intensity;
     }
@@ -21,39 +88,393 @@ impl PointLight {
     pub fn position(&self) -> Point {
         return self.position;
     }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+}
+
+// infinitely distant source such as the sun, every ray travels along `direction`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DirectionalLight {
+    direction: Vec3,
+    intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: Color) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.normalize(),
+            intensity,
+        }
+    }
+
+    pub fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.direction
+    }
+}
+
+// point light restricted to a cone, full intensity inside `inner_angle` fading to nothing
+// at `outer_angle` (both measured from the axis, in radians)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SpotLight {
+    position: Point,
+    direction: Vec3,
+    inner_angle: f64,
+    outer_angle: f64,
+    intensity: Color,
+    attenuation: Attenuation,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point,
+        direction: Vec3,
+        inner_angle: f64,
+        outer_angle: f64,
+        intensity: Color,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            inner_angle: inner_angle.min(outer_angle),
+            outer_angle,
+            intensity,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    pub fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.direction
+    }
+
+    // smoothstep between the outer and inner cone
+    pub fn falloff(&self, lightv: Vec3) -> f64 {
+        let cos_angle = -lightv ^ self.direction;
+        let (cos_outer, cos_inner) = (self.outer_angle.cos(), self.inner_angle.cos());
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        let x = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        x * x * (3.0 - 2.0 * x)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AreaShape {
+    // parallelogram spanned by `uvec` and `vvec` from `corner`
+    Rectangle {
+        corner: Point,
+        uvec: Vec3,
+        vvec: Vec3,
+    },
+    Sphere {
+        center: Point,
+        radius: f64,
+    },
+}
+
+// emitter with a surface, shadow rays are traced toward one jittered point per
+// usteps x vsteps cell so that partially covered lights give soft shadows
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AreaLight {
+    shape: AreaShape,
+    usteps: usize,
+    vsteps: usize,
+    intensity: Color,
+    attenuation: Attenuation,
+}
+
+impl AreaLight {
+    pub fn rectangle(
+        corner: Point,
+        uvec: Vec3,
+        usteps: usize,
+        vvec: Vec3,
+        vsteps: usize,
+        intensity: Color,
+    ) -> AreaLight {
+        AreaLight {
+            shape: AreaShape::Rectangle { corner, uvec, vvec },
+            usteps: usteps.max(1),
+            vsteps: vsteps.max(1),
+            intensity,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn sphere(
+        center: Point,
+        radius: f64,
+        usteps: usize,
+        vsteps: usize,
+        intensity: Color,
+    ) -> AreaLight {
+        AreaLight {
+            shape: AreaShape::Sphere { center, radius },
+            usteps: usteps.max(1),
+            vsteps: vsteps.max(1),
+            intensity,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    pub fn shape(&self) -> AreaShape {
+        self.shape
+    }
+
+    pub fn samples(&self) -> usize {
+        self.usteps * self.vsteps
+    }
+
+    pub fn center(&self) -> Point {
+        match self.shape {
+            AreaShape::Rectangle { corner, uvec, vvec } => corner + (uvec + vvec) * 0.5,
+            AreaShape::Sphere { center, .. } => center,
+        }
+    }
+
+    // one jittered position per cell, as seen from `point`
+    pub fn positions(&self, point: Point, seed: u32) -> Vec<Point> {
+        let mut positions = Vec::with_capacity(self.samples());
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                let cell = v * self.usteps + u;
+                let (ju, jv) = random_2d(seed, cell, 0);
+                let cu = (u as f64 + ju) / self.usteps as f64;
+                let cv = (v as f64 + jv) / self.vsteps as f64;
+                positions.push(self.position_on(point, cu, cv));
+            }
+        }
+        positions
+    }
+
+    fn position_on(&self, point: Point, u: f64, v: f64) -> Point {
+        match self.shape {
+            AreaShape::Rectangle { corner, uvec, vvec } => corner + uvec * u + vvec * v,
+            // a sphere seen from outside covers the same directions as the disk through its
+            // center facing the viewer
+            AreaShape::Sphere { center, radius } => {
+                let (tangent, bitangent) = orthonormal_basis((point - center).normalize());
+                let (dx, dy) = concentric_disk(u, v);
+                center + tangent * (dx * radius) + bitangent * (dy * radius)
+            }
+        }
+    }
+}
+
+// what a light contributes toward a shading point
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LightSample {
+    // unit vector from the shading point toward the light
+    pub direction: Vec3,
+    // f64::INFINITY for lights without a position
+    pub distance: f64,
+    pub intensity: Color,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Light {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Area(AreaLight),
+}
+
+impl Light {
+    pub fn point(position: Point, intensity: Color) -> Light {
+        Light::Point(PointLight::new(position, intensity))
+    }
+
+    pub fn directional(direction: Vec3, intensity: Color) -> Light {
+        Light::Directional(DirectionalLight::new(direction, intensity))
+    }
+
+    pub fn spot(
+        position: Point,
+        direction: Vec3,
+        inner_angle: f64,
+        outer_angle: f64,
+        intensity: Color,
+    ) -> Light {
+        Light::Spot(SpotLight::new(
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+            intensity,
+        ))
+    }
+
+    // unattenuated emitted intensity
+    pub fn intensity(&self) -> Color {
+        match self {
+            Light::Point(l) => l.intensity(),
+            Light::Directional(l) => l.intensity(),
+            Light::Spot(l) => l.intensity(),
+            Light::Area(l) => l.intensity(),
+        }
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        match self {
+            Light::Point(l) => l.attenuation(),
+            Light::Directional(_) => Attenuation::default(),
+            Light::Spot(l) => l.attenuation(),
+            Light::Area(l) => l.attenuation(),
+        }
+    }
+
+    // (unit direction, distance) of the shadow rays from `point` that decide how much of
+    // the light is visible, `seed` drives the jitter of area lights
+    pub fn shadow_rays(&self, point: Point, seed: u32) -> Vec<(Vec3, f64)> {
+        let toward = |target: Point| {
+            let v = target - point;
+            (v.normalize(), v.magnitude())
+        };
+        match self {
+            Light::Point(l) => vec![toward(l.position())],
+            Light::Spot(l) => vec![toward(l.position())],
+            Light::Directional(l) => vec![(-l.direction(), f64::INFINITY)],
+            Light::Area(l) => l.positions(point, seed).into_iter().map(toward).collect(),
+        }
+    }
+
+    pub fn sample(&self, point: Point) -> LightSample {
+        match self {
+            Light::Point(l) => {
+                let to_light = l.position() - point;
+                LightSample {
+                    direction: to_light.normalize(),
+                    distance: to_light.magnitude(),
+                    intensity: l.intensity(),
+                }
+            }
+            Light::Directional(l) => LightSample {
+                direction: -l.direction(),
+                distance: f64::INFINITY,
+                intensity: l.intensity(),
+            },
+            Light::Spot(l) => {
+                let to_light = l.position() - point;
+                let direction = to_light.normalize();
+                LightSample {
+                    direction,
+                    distance: to_light.magnitude(),
+                    intensity: l.intensity() * l.falloff(direction),
+                }
+            }
+            Light::Area(l) => {
+                let to_light = l.center() - point;
+                LightSample {
+                    direction: to_light.normalize(),
+                    distance: to_light.magnitude(),
+                    intensity: l.intensity(),
+                }
+            }
+        }
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(light: AreaLight) -> Self {
+        Light::Area(light)
+    }
 }
 
 // phong shading algorithm
+// `visibility` is the fraction of the light reaching the point per channel, black when fully
+// in shadow
 pub fn lighting(
     m: Material,
-    light: &PointLight,
+    light: &Light,
     position: Point,
     eyev: Vec3,
     normalv: Vec3,
+    visibility: Color,
 ) -> Color {
     let mut diffuse = Color::black();
     let mut ambient = Color::black();
     let mut specular = Color::black();
 
-    let effective_color = m.color * light.intensity();
-    let lightv = (light.position - position).normalize();
+    let mut sample = light.sample(position);
+    sample.intensity *= light.attenuation().factor(sample.distance);
+    let effective_color = m.color * sample.intensity;
+    let lightv = sample.direction;
     ambient = effective_color * m.ambient;
 
     let light_dot_normal = lightv ^ normalv;
-    if light_dot_normal < 0.0 {
+    if let ShadingModel::Microfacet { .. } = m.model {
+        // pi matches the normalization of `Material::brdf`
+        if light_dot_normal > 0.0 {
+            let f = m.brdf(normalv, eyev, lightv);
+            return ambient + sample.intensity * visibility * f * (PI * light_dot_normal);
+        }
+        return ambient;
+    }
+    if light_dot_normal < 0.0 || visibility == Color::black() {
         diffuse = Color::black();
         specular = Color::black();
     } else {
-        diffuse = effective_color * m.diffuse * light_dot_normal;
-        let reflectv = Vec3::reflect(-lightv, normalv);
-        let reflect_dot_eye = reflectv ^ eyev;
-
-        if reflect_dot_eye <= 0.0 {
-            specular = Color::black();
-        } else {
-            let factor = reflect_dot_eye.powf(m.shininess);
-            specular = light.intensity() * m.specular * factor;
-        }
+        let roughness = m.diffuse_factor(normalv, eyev, lightv);
+        diffuse = effective_color * visibility * (m.diffuse * light_dot_normal * roughness);
+        let factor = m.highlight(normalv, eyev, lightv);
+        specular = sample.intensity * visibility * (m.specular * factor);
     }
 
     return ambient + diffuse + specular;
@@ -66,6 +487,25 @@ pub struct Material {
     pub diffuse: f64,
     pub specular: f64,
     pub shininess: f64,
+    // light given off by the surface itself, scaled by `emission_strength`
+    pub emission: Color,
+    pub emission_strength: f64,
+    pub reflective: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    // wavelength dependence of the refractive index, seen by spectral renders
+    pub dispersion: Dispersion,
+    // Beer-Lambert absorption coefficient per unit of distance travelled inside the object,
+    // larger values darken thick parts more
+    pub absorption: Color,
+    // spreads the reflective and transparent lobes around the mirror and refracted directions
+    // with a GGX microfacet lobe, 0 keeps them sharp. `glossy_samples` directions are averaged
+    pub roughness: f64,
+    pub glossy_samples: usize,
+    // replaces the diffuse term with light scattered beneath the surface. Phong family only,
+    // the microfacet model keeps its own diffuse lobe and ignores it
+    pub subsurface: Option<Subsurface>,
+    pub model: ShadingModel,
 }
 
 impl Default for Material {
@@ -76,6 +516,17 @@ impl Default for Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            emission: Color::black(),
+            emission_strength: 1.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            dispersion: Dispersion::None,
+            absorption: Color::black(),
+            roughness: 0.0,
+            glossy_samples: 8,
+            subsurface: None,
+            model: ShadingModel::default(),
         }
     }
 }
@@ -88,6 +539,139 @@ impl Material {
             diffuse,
             specular,
             shininess,
+            ..Default::default()
+        }
+    }
+
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        let e = self.emitted();
+        e.r > 0.0 || e.g > 0.0 || e.b > 0.0
+    }
+
+    // the scattering beneath the surface that the shading model takes, see `subsurface`
+    pub fn subsurface_scattering(&self) -> Option<Subsurface> {
+        match self.model {
+            ShadingModel::Microfacet { .. } => None,
+            _ => self.subsurface,
+        }
+    }
+
+    // what is left on top of the surface when the diffuse term comes from beneath it
+    pub fn shell(&self) -> Material {
+        match self.subsurface_scattering() {
+            Some(_) => Material {
+                diffuse: 0.0,
+                ..*self
+            },
+            None => *self,
+        }
+    }
+
+    // reflectance for light arriving along `lightv` and leaving along `eyev`. The Phong family
+    // is the diffuse term plus a normalized highlight lobe, scaled so that radiance 1 from every
+    // direction reflects like a head-on light of intensity 1 does in `lighting`
+    pub fn brdf(&self, normalv: Vec3, eyev: Vec3, lightv: Vec3) -> Color {
+        match self.model {
+            ShadingModel::Microfacet {
+                metallic,
+                roughness,
+            } => microfacet_brdf(self.color, metallic, roughness, normalv, eyev, lightv),
+            _ => {
+                let diffuse =
+                    self.color * (self.diffuse / PI * self.diffuse_factor(normalv, eyev, lightv));
+                // energy normalization of the cosine lobe of each highlight
+                let norm = match self.model {
+                    ShadingModel::Phong {
+                        specular: SpecularModel::BlinnPhong,
+                        ..
+                    } => (self.shininess + 8.0) / (8.0 * PI),
+                    _ => (self.shininess + 2.0) / (2.0 * PI),
+                };
+                let highlight = self.highlight(normalv, eyev, lightv);
+                diffuse + Color::white() * (self.specular * norm * highlight)
+            }
+        }
+    }
+
+    // scale on the Lambertian term, 1 unless the diffuse is rough
+    fn diffuse_factor(&self, normalv: Vec3, eyev: Vec3, lightv: Vec3) -> f64 {
+        match self.model {
+            ShadingModel::Phong {
+                diffuse: DiffuseModel::OrenNayar { sigma },
+                ..
+            } => oren_nayar(sigma, normalv, eyev, lightv),
+            _ => 1.0,
+        }
+    }
+
+    // specular highlight before `specular` scaling, reflect-vector Phong unless Blinn-Phong
+    fn highlight(&self, normalv: Vec3, eyev: Vec3, lightv: Vec3) -> f64 {
+        if let ShadingModel::Phong {
+            specular: SpecularModel::BlinnPhong,
+            ..
+        } = self.model
+        {
+            return blinn_phong(normalv, eyev, lightv, self.shininess);
+        }
+        let reflect_dot_eye = Vec3::reflect(-lightv, normalv) ^ eyev;
+        if reflect_dot_eye <= 0.0 {
+            return 0.0;
+        }
+        reflect_dot_eye.powf(self.shininess)
+    }
+
+    // direction toward the light importance sampled for `brdf`
+    pub fn sample_direction(&self, normalv: Vec3, eyev: Vec3, rng: &mut Rng) -> Vec3 {
+        let (u, v) = rng.next_2d();
+        match self.model {
+            ShadingModel::Microfacet {
+                metallic,
+                roughness,
+            } => {
+                let choice = rng.next_f64();
+                sample_microfacet(metallic, roughness, normalv, eyev, choice, u, v)
+            }
+            _ => cosine_hemisphere(normalv, u, v),
+        }
+    }
+
+    // refractive index seen by a ray of `wavelength` nanometers, if it has one
+    pub fn refractive_index_at(&self, wavelength: Option<f64>) -> f64 {
+        wavelength
+            .and_then(|l| self.dispersion.index(l))
+            .unwrap_or(self.refractive_index)
+    }
+
+    // fraction of each channel left after travelling `distance` through the material
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let a = self.absorption;
+        Color::new(
+            f64::exp(-a.r * distance),
+            f64::exp(-a.g * distance),
+            f64::exp(-a.b * distance),
+        )
+    }
+
+    // normal to reflect or refract about, perturbed by `roughness`
+    pub fn glossy_normal(&self, normalv: Vec3, u: f64, v: f64) -> Vec3 {
+        if self.roughness <= 0.0 {
+            return normalv;
+        }
+        sample_ggx_normal(normalv, self.roughness, u, v)
+    }
+
+    // solid angle density of `sample_direction`
+    pub fn pdf(&self, normalv: Vec3, eyev: Vec3, lightv: Vec3) -> f64 {
+        match self.model {
+            ShadingModel::Microfacet {
+                metallic,
+                roughness,
+            } => microfacet_pdf(metallic, roughness, normalv, eyev, lightv),
+            _ => f64::max(0.0, lightv ^ normalv) / PI,
         }
     }
 }
@@ -96,7 +680,7 @@ impl Material {
 mod tests {
     use std::f64::consts::SQRT_2;
 
-    use crate::primitives::tuple::Tuple;
+    use crate::primitives::{float::ApproxEq, tuple::Tuple};
 
     use super::*;
 
@@ -120,6 +704,22 @@ mod tests {
         assert_eq!(m.diffuse, 0.9);
         assert_eq!(m.specular, 0.9);
         assert_eq!(m.shininess, 200.0);
+        assert_eq!(m.emission, Color::black());
+        assert!(!m.is_emissive());
+        assert_eq!(m.reflective, 0.0);
+        assert_eq!(m.transparency, 0.0);
+        assert_eq!(m.refractive_index, 1.0);
+    }
+
+    #[test]
+    fn emission_is_scaled_by_strength() {
+        let m = Material {
+            emission: Color::new(1.0, 0.5, 0.25),
+            emission_strength: 4.0,
+            ..Default::default()
+        };
+        assert!(m.is_emissive());
+        assert_eq!(m.emitted(), Color::new(4.0, 2.0, 1.0));
     }
 
     #[allow(dead_code)]
@@ -133,9 +733,9 @@ mod tests {
 
         let eyev = Vec3::new(0.0, 0.0, -1.0);
         let normalv = Vec3::new(0.0, 0.0, -1.0);
-        let light = PointLight::new(Point::new(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let result = lighting(m, &light, p, eyev, normalv);
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
 
         assert_eq!(result, Color::new(1.9, 1.9, 1.9));
     }
@@ -145,9 +745,9 @@ mod tests {
         let (m, p) = setup();
         let eyev = Vec3::new(0.0, SQRT_2 / 2.0, -SQRT_2 / 2.0);
         let normalv = Vec3::new(0.0, 0.0, -1.0);
-        let light = PointLight::new(Point::new(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let result = lighting(m, &light, p, eyev, normalv);
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
 
         assert_eq!(result, Color::new(1.0, 1.0, 1.0));
     }
@@ -158,21 +758,49 @@ mod tests {
 
         let eyev = Vec3::new(0.0, 0.0, -1.0);
         let normalv = Vec3::new(0.0, 0.0, -1.0);
-        let light = PointLight::new(Point::new(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let light = Light::point(Point::new(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let result = lighting(m, &light, p, eyev, normalv);
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
 
         assert_eq!(result, Color::new(0.7364, 0.7364, 0.7364));
     }
 
+    #[test]
+    fn transmittance_falls_off_exponentially() {
+        let m = Material {
+            absorption: Color::new(0.0, 1.0, 2.0),
+            ..Default::default()
+        };
+        assert_eq!(m.transmittance(0.0), Color::white());
+        assert_eq!(
+            m.transmittance(0.5),
+            Color::new(1.0, f64::exp(-0.5), f64::exp(-1.0))
+        );
+        assert_eq!(Material::default().transmittance(10.0), Color::white());
+    }
+
+    #[test]
+    fn glossy_normal_is_sharp_without_roughness() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(Material::default().glossy_normal(n, 0.3, 0.7), n);
+
+        let rough = Material {
+            roughness: 0.5,
+            ..Default::default()
+        };
+        let h = rough.glossy_normal(n, 0.3, 0.7);
+        assert!(h != n && (h ^ n) > 0.0);
+        assert!(h.magnitude().approx_eq(1.0));
+    }
+
     #[test]
     fn lighting_with_eye_in_path_of_reflection_vector() {
         let (m, p) = setup();
         let eyev = Vec3::new(0.0, -SQRT_2 / 2.0, -SQRT_2 / 2.0);
         let normalv = Vec3::new(0.0, 0.0, -1.0);
-        let light = PointLight::new(Point::new(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let light = Light::point(Point::new(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let result = lighting(m, &light, p, eyev, normalv);
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
 
         assert_eq!(result, Color::new(1.6364, 1.6364, 1.6364));
     }
@@ -182,10 +810,343 @@ mod tests {
         let (m, p) = setup();
         let eyev = Vec3::new(0.0, 0.0, -1.0);
         let normalv = Vec3::new(0.0, 0.0, -1.0);
-        let light = PointLight::new(Point::new(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
+        let light = Light::point(Point::new(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
+
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
+
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn point_light_sample_points_toward_light() {
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::white());
+        let sample = light.sample(Point::zero());
+
+        assert_eq!(sample.direction, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(sample.distance, 10.0);
+        assert_eq!(sample.intensity, Color::white());
+    }
+
+    #[test]
+    fn directional_light_is_the_same_everywhere() {
+        let light = Light::directional(Vec3::new(0.0, -2.0, 0.0), Color::white());
+        let a = light.sample(Point::zero());
+        let b = light.sample(Point::new(100.0, -3.0, 7.0));
+
+        assert_eq!(a, b);
+        assert_eq!(a.direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(a.distance, f64::INFINITY);
+    }
+
+    #[test]
+    fn lighting_with_directional_light_overhead() {
+        let (m, p) = setup();
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::directional(Vec3::new(0.0, 0.0, 1.0), Color::white());
+
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
+
+        assert_eq!(result, Color::new(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn spot_light_falls_off_between_inner_and_outer_cone() {
+        let light = Light::spot(
+            Point::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            PI / 8.0,
+            PI / 4.0,
+            Color::white(),
+        );
+
+        let on_axis = light.sample(Point::zero());
+        let inside_inner = light.sample(Point::new(2.0, 0.0, 0.0));
+        let penumbra = light.sample(Point::new(7.0, 0.0, 0.0));
+        let outside = light.sample(Point::new(20.0, 0.0, 0.0));
+
+        assert_eq!(on_axis.intensity, Color::white());
+        assert_eq!(inside_inner.intensity, Color::white());
+        assert!(penumbra.intensity.r > 0.0 && penumbra.intensity.r < 1.0);
+        assert_eq!(outside.intensity, Color::black());
+    }
+
+    #[test]
+    fn lighting_outside_spot_cone_is_black() {
+        let (m, p) = setup();
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::spot(
+            Point::new(0.0, 0.0, -10.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            PI / 8.0,
+            PI / 4.0,
+            Color::white(),
+        );
+
+        let result = lighting(m, &light, p, eyev, normalv, Color::white());
+
+        assert_eq!(result, Color::black());
+    }
+
+    #[test]
+    fn lighting_with_surface_in_shadow() {
+        let (m, p) = setup();
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::white());
 
-        let result = lighting(m, &light, p, eyev, normalv);
+        let result = lighting(m, &light, p, eyev, normalv, Color::black());
 
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn lighting_scales_diffuse_and_specular_by_visibility() {
+        let (m, p) = setup();
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::white());
+
+        let result = lighting(m, &light, p, eyev, normalv, Color::new(0.5, 0.5, 0.5));
+
+        assert_eq!(result, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rectangle_area_light_samples_one_point_per_cell() {
+        let light = AreaLight::rectangle(
+            Point::zero(),
+            Vec3::new(2.0, 0.0, 0.0),
+            4,
+            Vec3::new(0.0, 0.0, 1.0),
+            2,
+            Color::white(),
+        );
+        let positions = light.positions(Point::new(0.0, -5.0, 0.0), 11);
+
+        assert_eq!(light.samples(), 8);
+        assert_eq!(light.center(), Point::new(1.0, 0.0, 0.5));
+        for (i, p) in positions.iter().enumerate() {
+            let (u, v) = ((i % 4) as f64, (i / 4) as f64);
+            assert!(p.x() >= u * 0.5 && p.x() <= (u + 1.0) * 0.5);
+            assert!(p.z() >= v * 0.5 && p.z() <= (v + 1.0) * 0.5);
+            assert_eq!(p.y(), 0.0);
+        }
+    }
+
+    #[test]
+    fn sphere_area_light_samples_disk_facing_the_point() {
+        let light = AreaLight::sphere(Point::new(0.0, 10.0, 0.0), 2.0, 3, 3, Color::white());
+        for p in light.positions(Point::zero(), 5) {
+            assert!(p.y().approx_eq_low_precision(10.0));
+            assert!((p - light.center()).magnitude() <= 2.0 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn area_light_shadow_rays_point_at_its_positions() {
+        let light = Light::from(AreaLight::rectangle(
+            Point::new(-1.0, 5.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            2,
+            Vec3::new(0.0, 0.0, 2.0),
+            2,
+            Color::white(),
+        ));
+        let rays = light.shadow_rays(Point::zero(), 3);
+
+        assert_eq!(rays.len(), 4);
+        for (direction, distance) in rays {
+            assert!(direction.magnitude().approx_eq(1.0));
+            assert!(distance >= 5.0);
+        }
+    }
+
+    #[test]
+    fn default_attenuation_is_constant() {
+        let a = Attenuation::default();
+        assert_eq!(a.factor(0.5), 1.0);
+        assert_eq!(a.factor(100.0), 1.0);
+        assert_eq!(a.factor(f64::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn inverse_square_attenuation() {
+        let a = Attenuation::inverse_square();
+        assert!(a.factor(2.0).approx_eq(0.25));
+        assert!(a.factor(10.0).approx_eq(0.01));
+        assert_eq!(a.factor(f64::INFINITY), 0.0);
+        // touching the light is as bright as being at the minimum distance
+        assert_eq!(a.factor(0.0), a.factor(MIN_DISTANCE));
+        assert!(a.factor(0.0).is_finite());
+    }
+
+    #[test]
+    fn constant_linear_quadratic_attenuation() {
+        let a = Attenuation::new(1.0, 0.5, 0.25);
+        assert!(a.factor(2.0).approx_eq(1.0 / 3.0));
+    }
+
+    #[test]
+    fn lighting_with_attenuated_light_depends_on_distance() {
+        let (m, p) = setup();
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = |z: f64| {
+            Light::from(
+                PointLight::new(Point::new(0.0, 0.0, z), Color::new(4.0, 4.0, 4.0))
+                    .with_attenuation(Attenuation::inverse_square()),
+            )
+        };
+
+        let near = lighting(m, &light(-2.0), p, eyev, normalv, Color::white());
+        let far = lighting(m, &light(-4.0), p, eyev, normalv, Color::white());
+
+        // ambient 0.1, diffuse 0.9 and specular 0.9, all scaled by 4 / d^2
+        assert_eq!(near, Color::new(1.9, 1.9, 1.9));
+        assert_eq!(far, Color::new(0.475, 0.475, 0.475));
+    }
+
+    #[test]
+    fn brdf_is_lambertian_without_specular() {
+        let m = Material {
+            color: Color::new(0.5, 1.0, 0.25),
+            diffuse: 0.8,
+            specular: 0.0,
+            ..Default::default()
+        };
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let f = m.brdf(n, Vec3::new(0.3, 1.0, 0.0).normalize(), n);
+        assert_eq!(f, Color::new(0.4, 0.8, 0.2) * (1.0 / PI));
+    }
+
+    #[test]
+    fn brdf_specular_peaks_along_the_mirror_direction() {
+        let m = Material::default();
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let lightv = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let mirror = m.brdf(n, Vec3::new(-1.0, 1.0, 0.0).normalize(), lightv);
+        let off = m.brdf(n, Vec3::new(-1.0, 2.0, 0.0).normalize(), lightv);
+        assert!(mirror.r > off.r);
+        assert!(off.r > m.color.r * m.diffuse / PI);
+    }
+
+    #[test]
+    fn lighting_with_microfacet_model() {
+        let m = Material {
+            model: ShadingModel::Microfacet {
+                metallic: 0.0,
+                roughness: 0.5,
+            },
+            ..Default::default()
+        };
+        let position = Point::new(0.0, 0.0, 0.0);
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 10.0, -10.0), Color::white());
+        let lightv = Vec3::new(0.0, 1.0, -1.0).normalize();
+
+        let lit = lighting(m, &light, position, eyev, normalv, Color::white());
+        let expected = m.color * m.ambient + m.brdf(normalv, eyev, lightv) * (PI * SQRT_2 / 2.0);
+        assert_eq!(lit, expected);
+        assert_eq!(
+            lighting(m, &light, position, eyev, normalv, Color::black()),
+            m.color * m.ambient
+        );
+    }
+
+    #[test]
+    fn lighting_with_blinn_phong_highlight() {
+        let m = Material {
+            model: ShadingModel::Phong {
+                diffuse: DiffuseModel::Lambert,
+                specular: SpecularModel::BlinnPhong,
+            },
+            ..Default::default()
+        };
+        let position = Point::new(0.0, 0.0, 0.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 10.0, -10.0), Color::white());
+
+        // eye in the path of the reflection vector, where the half vector is the normal
+        let eyev = Vec3::new(0.0, -SQRT_2 / 2.0, -SQRT_2 / 2.0);
+        let result = lighting(m, &light, position, eyev, normalv, Color::white());
+        assert_eq!(result, Color::new(1.6364, 1.6364, 1.6364));
+
+        // eye straight on, Blinn-Phong still catches some of the highlight unlike Phong
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let blinn = lighting(m, &light, position, eyev, normalv, Color::white());
+        let phong = lighting(
+            Material::default(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            Color::white(),
+        );
+        assert!(blinn.r > phong.r);
+    }
+
+    #[test]
+    fn lighting_with_oren_nayar_diffuse() {
+        let rough = Material {
+            model: ShadingModel::Phong {
+                diffuse: DiffuseModel::OrenNayar { sigma: 0.5 },
+                specular: SpecularModel::Phong,
+            },
+            specular: 0.0,
+            ..Default::default()
+        };
+        let smooth = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let position = Point::new(0.0, 0.0, 0.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 0.0, -10.0), Color::white());
+
+        let r = lighting(rough, &light, position, eyev, normalv, Color::white());
+        let s = lighting(smooth, &light, position, eyev, normalv, Color::white());
+        assert!(r.r < s.r);
+
+        let flat = Material {
+            model: ShadingModel::Phong {
+                diffuse: DiffuseModel::OrenNayar { sigma: 0.0 },
+                specular: SpecularModel::Phong,
+            },
+            ..smooth
+        };
+        assert_eq!(
+            lighting(flat, &light, position, eyev, normalv, Color::white()),
+            s
+        );
+    }
+
+    #[test]
+    fn oren_nayar_diffuse_combines_with_blinn_phong_highlight() {
+        let model = |diffuse, specular| Material {
+            model: ShadingModel::Phong { diffuse, specular },
+            ..Default::default()
+        };
+        let rough = DiffuseModel::OrenNayar { sigma: 0.5 };
+        let position = Point::new(0.0, 0.0, 0.0);
+        let normalv = Vec3::new(0.0, 0.0, -1.0);
+        let eyev = Vec3::new(0.0, 0.0, -1.0);
+        let light = Light::point(Point::new(0.0, 10.0, -10.0), Color::white());
+        let shade = |m| lighting(m, &light, position, eyev, normalv, Color::white());
+
+        let both = shade(model(rough, SpecularModel::BlinnPhong));
+        let rough_phong = shade(model(rough, SpecularModel::Phong));
+        let lambert_blinn = shade(model(DiffuseModel::Lambert, SpecularModel::BlinnPhong));
+        let lambert_phong = shade(Material::default());
+
+        // each choice changes its own term only
+        assert!((both - rough_phong)
+            .r
+            .approx_eq(lambert_blinn.r - lambert_phong.r));
+        assert!(both.r > rough_phong.r);
+        assert!(both.r != lambert_blinn.r);
+    }
 }
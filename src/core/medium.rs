@@ -0,0 +1,181 @@
+use std::f64::consts::PI;
+
+use super::{object::Object, ray::Ray};
+use crate::primitives::color::Color;
+
+// homogeneous participating medium such as fog or smoke. `scattering` and `absorption` are per
+// unit of distance and per channel, `asymmetry` is the Henyey-Greenstein g, from -1 (light
+// scatters back) through 0 (evenly) to 1 (light keeps going forward)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Medium {
+    scattering: Color,
+    absorption: Color,
+    asymmetry: f64,
+    // ray marching steps per segment of a ray crossing the medium
+    steps: usize,
+}
+
+impl Medium {
+    pub fn new(scattering: Color, absorption: Color) -> Self {
+        Medium {
+            scattering,
+            absorption,
+            asymmetry: 0.0,
+            steps: 16,
+        }
+    }
+
+    pub fn with_asymmetry(mut self, asymmetry: f64) -> Self {
+        self.asymmetry = asymmetry.clamp(-0.99, 0.99);
+        self
+    }
+
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.steps = steps.max(1);
+        self
+    }
+
+    pub fn scattering(&self) -> Color {
+        self.scattering
+    }
+
+    pub fn absorption(&self) -> Color {
+        self.absorption
+    }
+
+    pub fn asymmetry(&self) -> f64 {
+        self.asymmetry
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    // light lost per unit of distance, to absorption and to scattering out of the ray
+    pub fn extinction(&self) -> Color {
+        self.scattering + self.absorption
+    }
+
+    // density of light travelling along one direction being scattered into another, with
+    // `cos_theta` the cosine between the two
+    pub fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.asymmetry;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+// Beer-Lambert law, fraction of each channel left after `distance` through `extinction`.
+// The distance may be infinite, channels that nothing takes away from stay at 1
+pub fn beer_lambert(extinction: Color, distance: f64) -> Color {
+    let channel = |sigma: f64| {
+        if sigma == 0.0 {
+            1.0
+        } else {
+            f64::exp(-sigma * distance)
+        }
+    };
+    Color::new(
+        channel(extinction.r),
+        channel(extinction.g),
+        channel(extinction.b),
+    )
+}
+
+// a medium filling the inside of `boundary`. The boundary only marks where the medium is, it
+// is never shaded and casts no shadows of its own
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Volume {
+    boundary: Object,
+    medium: Medium,
+}
+
+impl Volume {
+    pub fn new(boundary: Object, medium: Medium) -> Self {
+        Volume { boundary, medium }
+    }
+
+    pub fn boundary(&self) -> &Object {
+        &self.boundary
+    }
+
+    pub fn medium(&self) -> &Medium {
+        &self.medium
+    }
+
+    // ranges of `t` along `ray` that lie inside the boundary
+    pub fn spans(&self, ray: &Ray) -> Vec<(f64, f64)> {
+        let result = self.boundary.intersect(ray);
+        if !result.ok {
+            return vec![];
+        }
+        let mut ts: Vec<f64> = result.i.iter().map(|i| i.t).collect();
+        ts.sort_by(|a, b| a.total_cmp(b));
+        ts.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{random::Rng, sampling::uniform_sphere, transforms::Transformations},
+        primitives::{float::ApproxEq, point::Point, tuple::Tuple, vec3::Vec3},
+    };
+
+    #[test]
+    fn extinction_adds_scattering_and_absorption() {
+        let m = Medium::new(Color::new(0.1, 0.2, 0.3), Color::new(0.3, 0.2, 0.1));
+        assert_eq!(m.extinction(), Color::new(0.4, 0.4, 0.4));
+    }
+
+    #[test]
+    fn phase_function_is_normalized() {
+        let mut rng = Rng::new(5, 0);
+        for g in [0.0, 0.5, -0.3] {
+            let m = Medium::new(Color::white(), Color::black()).with_asymmetry(g);
+            let forward = Vec3::new(0.0, 0.0, 1.0);
+            let n = 20000;
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let (u, v) = rng.next_2d();
+                sum += m.phase(uniform_sphere(u, v) ^ forward) * 4.0 * PI / n as f64;
+            }
+            assert!((sum - 1.0).abs() < 0.03, "g = {g}: {sum}");
+        }
+    }
+
+    #[test]
+    fn positive_asymmetry_scatters_forward() {
+        let m = Medium::new(Color::white(), Color::black()).with_asymmetry(0.7);
+        assert!(m.phase(1.0) > m.phase(0.0));
+        assert!(m.phase(0.0) > m.phase(-1.0));
+        let isotropic = Medium::new(Color::white(), Color::black());
+        assert!(isotropic.phase(0.3).approx_eq(1.0 / (4.0 * PI)));
+    }
+
+    #[test]
+    fn beer_lambert_handles_endless_distances() {
+        let extinction = Color::new(0.0, 1.0, 2.0);
+        assert_eq!(
+            beer_lambert(extinction, 1.0),
+            Color::new(1.0, f64::exp(-1.0), f64::exp(-2.0))
+        );
+        assert_eq!(
+            beer_lambert(extinction, f64::INFINITY),
+            Color::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn volume_spans_cover_the_inside_of_the_boundary() {
+        let mut boundary = Object::sphere();
+        boundary.set_transformation(Transformations::scale(2.0, 2.0, 2.0));
+        let v = Volume::new(boundary, Medium::new(Color::white(), Color::black()));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(v.spans(&r), vec![(3.0, 7.0)]);
+        let miss = Ray::new(Point::new(0.0, 5.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(v.spans(&miss).is_empty());
+    }
+}
@@ -1,21 +1,44 @@
-use crate::primitives::{color::Color, matrix4f::Matrix4f, point::Point, tuple::Tuple, vec3::Vec3};
-
-use super::{light::Material, ray::Ray, sphere::Sphere, transforms::Transform};
-
+use crate::primitives::{
+    color::Color, float::SURFACE_EPSILON, matrix4f::Matrix4f, point::Point, tuple::Tuple,
+    vec3::Vec3,
+};
+
+use super::{
+    light::Material,
+    ray::Ray,
+    sphere::Sphere,
+    transforms::{Decomposition, Transform},
+};
+
+#[derive(Clone, Copy)]
 pub struct IntersectionState<'a> {
     pub t: f64,
     pub object: &'a Object,
     pub point: Point,
+    // point nudged off the surface, origin for rays leaving it
+    pub over_point: Point,
+    // point nudged below the surface, origin for refracted rays
+    pub under_point: Point,
     pub normalv: Vec3,
     pub eyev: Vec3,
+    pub reflectv: Vec3,
     pub inside: bool,
+    pub time: f64,
+    // refractive indices on the incoming and outgoing side of the surface
+    pub n1: f64,
+    pub n2: f64,
+    pub wavelength: Option<f64>,
+    // seed of the ray that hit, see `Ray::seed`
+    pub seed: u32,
+    // fraction of the light surviving absorption on its way from the hit back to the ray origin
+    pub transmittance: Color,
 }
 
 impl<'a> IntersectionState<'a> {
     pub fn new(i: Intersection<'a>, ray: Ray) -> Self {
         let ray_pos = ray.position(i.t);
 
-        let mut normalv = i.object.normal_at(ray_pos);
+        let mut normalv = i.object.normal_at_time(ray_pos, ray.time());
         let eyev = -ray.direction();
         let mut inside = false;
 
@@ -28,13 +51,121 @@ impl<'a> IntersectionState<'a> {
             t: i.t,
             object: i.object,
             point: ray_pos,
+            over_point: ray_pos + normalv * SURFACE_EPSILON,
+            under_point: ray_pos + normalv * -SURFACE_EPSILON,
             normalv,
             inside,
             eyev,
+            reflectv: Vec3::reflect(ray.direction(), normalv),
+            time: ray.time(),
+            n1: 1.0,
+            n2: 1.0,
+            wavelength: ray.wavelength(),
+            seed: ray.seed(),
+            transmittance: Color::white(),
+        }
+    }
+
+    // like `new`, also finds the media on both sides of the hit from the sorted
+    // intersections `xs` of the same ray
+    pub fn prepare(i: Intersection<'a>, ray: Ray, xs: &Intersections<'a>) -> Self {
+        let mut state = IntersectionState::new(i, ray);
+        // objects the ray is inside of, with the `t` where it entered them
+        let mut containers: Vec<(&Object, f64)> = Vec::new();
+
+        for x in xs.intersections.iter() {
+            let is_hit = x.t == i.t && std::ptr::eq(x.object, i.object);
+            if is_hit {
+                state.n1 = containers.last().map_or(1.0, |(o, _)| {
+                    o.material().refractive_index_at(ray.wavelength())
+                });
+                if let Some((medium, entry)) = containers.last() {
+                    // only the part of the medium between the ray origin and the hit
+                    let distance = (i.t - entry.max(0.0)) * ray.direction().magnitude();
+                    state.transmittance = medium.material().transmittance(distance);
+                }
+            }
+
+            match containers
+                .iter()
+                .position(|(o, _)| std::ptr::eq(*o, x.object))
+            {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push((x.object, x.t)),
+            }
+
+            if is_hit {
+                state.n2 = containers.last().map_or(1.0, |(o, _)| {
+                    o.material().refractive_index_at(ray.wavelength())
+                });
+                break;
+            }
+        }
+
+        state
+    }
+
+    // ray leaving the hit, at the same instant and wavelength and with the same seed as the
+    // one that arrived
+    pub fn spawn_ray(&self, origin: Point, direction: Vec3) -> Ray {
+        Ray::new(origin, direction)
+            .with_time(self.time)
+            .with_wavelength(self.wavelength)
+            .with_seed(self.seed)
+    }
+
+    // mirror direction about a microfacet normal picked by (u, v) from the material's
+    // roughness lobe. A facet that would send the ray into the surface gives the smooth mirror
+    // direction instead
+    pub fn glossy_reflection(&self, u: f64, v: f64) -> Vec3 {
+        let normalv = self.object.material().glossy_normal(self.normalv, u, v);
+        let direction = Vec3::reflect(-self.eyev, normalv);
+        if (direction ^ self.normalv) > 0.0 {
+            direction
+        } else {
+            self.reflectv
+        }
+    }
+
+    // refracted direction through a microfacet normal picked by (u, v) from the material's
+    // roughness lobe, None under total internal reflection of the smooth surface. A facet that
+    // totally reflects or turns the ray back out gives the smooth refracted direction instead
+    pub fn glossy_refraction(&self, u: f64, v: f64) -> Option<Vec3> {
+        let n_ratio = self.n1 / self.n2;
+        let smooth = Vec3::refract(self.eyev, self.normalv, n_ratio)?;
+        let normalv = self.object.material().glossy_normal(self.normalv, u, v);
+        match Vec3::refract(self.eyev, normalv, n_ratio) {
+            Some(direction) if (direction ^ self.normalv) < 0.0 => Some(direction),
+            _ => Some(smooth),
+        }
+    }
+
+    // Schlick's approximation of the Fresnel reflectance
+    pub fn schlick(&self) -> f64 {
+        let mut cos = self.eyev ^ self.normalv;
+        if self.n1 > self.n2 {
+            let n = self.n1 / self.n2;
+            let sin2_t = n * n * (1.0 - cos * cos);
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+            cos = f64::sqrt(1.0 - sin2_t);
         }
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
 }
 
+// point picked on a surface, `pdf` is the probability density with respect to area
+#[derive(Debug, Copy, Clone)]
+pub struct SurfaceSample {
+    pub point: Point,
+    pub normal: Vec3,
+    pub pdf: f64,
+}
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Shape {
     Sphere(Sphere),
@@ -66,6 +197,32 @@ impl Shape {
             Shape::Sphere(s) => s.normal_at(p),
         }
     }
+
+    pub fn sample_surface(&self, u: f64, v: f64) -> SurfaceSample {
+        match self {
+            Shape::Sphere(s) => s.sample_surface(u, v),
+        }
+    }
+
+    pub fn uv_at(&self, p: Point) -> (f64, f64) {
+        match self {
+            Shape::Sphere(s) => s.uv_at(p),
+        }
+    }
+
+    // object space sphere enclosing the shape, as (center, radius)
+    pub fn bounding_sphere(&self) -> (Point, f64) {
+        match self {
+            Shape::Sphere(s) => (s.center(), s.radius()),
+        }
+    }
+
+    // density of `sample_surface` at object space point `p`
+    pub fn surface_pdf(&self, _p: Point) -> f64 {
+        match self {
+            Shape::Sphere(s) => 1.0 / s.area(),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -150,6 +307,38 @@ impl<'a> std::ops::Index<usize> for Intersections<'a> {
     }
 }
 
+// transformation blended from `start` at time 0 to `end` at time 1
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Motion {
+    start: Decomposition,
+    end: Decomposition,
+}
+
+impl Motion {
+    // None when either end squashes the object flat or shears it, or only one of them
+    // mirrors it, since the blend would then have to squash it flat on the way
+    pub fn new(start: Matrix4f, end: Matrix4f) -> Option<Self> {
+        let start = Decomposition::new(&start)?;
+        let end = Decomposition::new(&end)?;
+        if start.scale.x().signum() != end.scale.x().signum() {
+            return None;
+        }
+        Some(Motion { start, end })
+    }
+
+    pub fn transformation_at(&self, time: f64) -> Matrix4f {
+        self.at(time).compose()
+    }
+
+    pub fn inverse_at(&self, time: f64) -> Matrix4f {
+        self.at(time).compose_inverse()
+    }
+
+    fn at(&self, time: f64) -> Decomposition {
+        self.start.interpolate(&self.end, time.clamp(0.0, 1.0))
+    }
+}
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub struct Object {
     shape: Shape,
@@ -157,6 +346,7 @@ pub struct Object {
     transformation_inverse: Matrix4f,
     transformation_inverse_transposed: Matrix4f,
     material: Material,
+    motion: Option<Motion>,
 }
 
 impl Object {
@@ -168,6 +358,7 @@ impl Object {
             transformation_inverse: Matrix4f::identity(),
             transformation_inverse_transposed: Matrix4f::identity(),
             material: Material::default(),
+            motion: None,
         };
     }
 
@@ -175,6 +366,35 @@ impl Object {
         self.transformation = transformation;
         self.transformation_inverse = transformation.invert().unwrap_or(Matrix4f::identity());
         self.transformation_inverse_transposed = self.transformation_inverse.transpose();
+        self.motion = None;
+    }
+
+    // object moves from `start` to `end` while the shutter is open. None, with the object
+    // left as it was, when the motion is rejected by `Motion::new`
+    #[must_use]
+    pub fn set_motion(&mut self, start: Matrix4f, end: Matrix4f) -> Option<Motion> {
+        let motion = Motion::new(start, end)?;
+        self.set_transformation(start);
+        self.motion = Some(motion);
+        Some(motion)
+    }
+
+    pub fn motion(&self) -> Option<Motion> {
+        self.motion
+    }
+
+    fn transformation_at(&self, time: f64) -> Matrix4f {
+        match self.motion {
+            None => self.transformation,
+            Some(motion) => motion.transformation_at(time),
+        }
+    }
+
+    fn inverse_at(&self, time: f64) -> Matrix4f {
+        match self.motion {
+            None => self.transformation_inverse,
+            Some(motion) => motion.inverse_at(time),
+        }
     }
 
     pub fn set_material(&mut self, material: Material) {
@@ -200,20 +420,90 @@ impl Object {
     }
 
     pub fn intersect<'a>(&'a self, ray: &Ray) -> IntersectionResult<'a> {
-        let r = (*ray).transform(&self.transformation_inverse);
+        let r = (*ray).transform(&self.inverse_at(ray.time()));
         return self.shape.intersect(&r, self);
     }
 
     pub fn normal_at(&self, point: Point) -> Vec3 {
-        let obj_point = self.transformation_inverse * point;
+        self.normal_at_time(point, 0.0)
+    }
+
+    pub fn normal_at_time(&self, point: Point, time: f64) -> Vec3 {
+        let (inverse, inverse_transposed) = match self.motion {
+            None => (
+                self.transformation_inverse,
+                self.transformation_inverse_transposed,
+            ),
+            Some(_) => {
+                let inverse = self.inverse_at(time);
+                (inverse, inverse.transpose())
+            }
+        };
+        let obj_point = inverse * point;
         let obj_normal = self.shape.normal_at(obj_point);
-        let world_normal = self.transformation_inverse_transposed * obj_normal;
+        let world_normal = inverse_transposed * obj_normal;
         return Vec3::new(world_normal.x(), world_normal.y(), world_normal.z()).normalize();
     }
 
+    // texture coordinates of the world space `point` on the surface
+    pub fn uv_at(&self, point: Point, time: f64) -> (f64, f64) {
+        self.shape.uv_at(self.inverse_at(time) * point)
+    }
+
     pub fn shape(&self) -> Shape {
         return self.shape;
     }
+
+    // light emitted by the surface, lets the object act as a light source
+    pub fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // point on the surface in world space, with its density per unit of world-space area
+    pub fn sample_surface(&self, u: f64, v: f64, time: f64) -> SurfaceSample {
+        let local = self.shape.sample_surface(u, v);
+        let (normal, stretch) = self.area_stretch(local.normal, time);
+
+        SurfaceSample {
+            point: self.transformation_at(time) * local.point,
+            normal,
+            pdf: local.pdf / stretch,
+        }
+    }
+
+    // world space sphere enclosing the object at `time`, as (center, radius)
+    pub fn bounding_sphere(&self, time: f64) -> (Point, f64) {
+        let (center, radius) = self.shape.bounding_sphere();
+        let m = self.transformation_at(time);
+        let stretch = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .map(|axis| (m * axis).magnitude())
+        .fold(0.0, f64::max);
+        (m * center, radius * stretch)
+    }
+
+    // area density of `sample_surface` at the world space `point`
+    pub fn surface_pdf(&self, point: Point, time: f64) -> f64 {
+        let local = self.inverse_at(time) * point;
+        let (_, stretch) = self.area_stretch(self.shape.normal_at(local), time);
+        self.shape.surface_pdf(local) / stretch
+    }
+
+    // world normal and area scale factor for an object space surface element with `normal`
+    fn area_stretch(&self, normal: Vec3, time: f64) -> (Vec3, f64) {
+        let m = self.transformation_at(time);
+        let inverse_transposed = self.inverse_at(time).transpose();
+
+        // an object-space area element is stretched by |det| * |M^-T n|
+        let n = inverse_transposed * normal.normalize();
+        let n = Vec3::new(n.x(), n.y(), n.z());
+        let stretch = m.submatrix(3, 3).determinant().abs() * n.magnitude();
+        (n.normalize(), stretch)
+    }
 }
 
 pub trait IntersectionPush<'a> {
@@ -222,7 +512,10 @@ pub trait IntersectionPush<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::primitives::{point::Point, tuple::Tuple, vec3::Vec3};
+    use crate::{
+        core::spectrum::Dispersion,
+        primitives::{float::ApproxEq, point::Point, tuple::Tuple, vec3::Vec3},
+    };
 
     use super::*;
 
@@ -314,6 +607,303 @@ mod tests {
         assert_eq!(intersections.hit(), Some(&i4));
     }
 
+    #[test]
+    fn moving_object_is_intersected_where_it_is_at_ray_time() {
+        let mut s = Object::sphere();
+        assert!(s
+            .set_motion(
+                Matrix4f::identity(),
+                crate::core::transforms::Transformations::translate(4.0, 0.0, 0.0),
+            )
+            .is_some());
+        let r = Ray::new(Point::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(!s.intersect(&r).ok);
+        assert!(!s.intersect(&r.with_time(1.0)).ok);
+
+        let hit = s.intersect(&r.with_time(0.5));
+        assert!(hit.ok);
+        assert_eq!(hit.i[0].t, 4.0);
+        assert_eq!(
+            s.normal_at_time(Point::new(2.0, 0.0, -1.0), 0.5),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn motion_through_a_flat_transformation_is_rejected() {
+        use crate::core::transforms::Transformations;
+        let mut s = Object::sphere();
+        let sheared = Transformations::scale(1.0, 3.0, 1.0) * Transformations::rotate_z(0.6);
+        assert_eq!(Motion::new(sheared, sheared), None);
+        assert_eq!(
+            s.set_motion(Matrix4f::identity(), Transformations::scale(0.0, 1.0, 1.0)),
+            None
+        );
+        assert_eq!(
+            s.set_motion(Matrix4f::identity(), Transformations::scale(-1.0, 1.0, 1.0)),
+            None
+        );
+        assert_eq!(s.set_motion(sheared, sheared), None);
+        assert_eq!(s.motion(), None);
+        assert_eq!(*s.transformation(), Matrix4f::identity());
+    }
+
+    #[test]
+    fn surface_sample_density_accounts_for_scaling() {
+        let mut s = Object::sphere();
+        s.set_transformation(
+            crate::core::transforms::Transformations::translate(0.0, 5.0, 0.0)
+                * crate::core::transforms::Transformations::scale(2.0, 2.0, 2.0),
+        );
+        let sample = s.sample_surface(0.25, 0.5, 0.0);
+
+        assert!((sample.point - Point::new(0.0, 5.0, 0.0))
+            .magnitude()
+            .approx_eq(2.0));
+        assert_eq!(sample.normal, s.normal_at(sample.point));
+        assert!((sample.pdf - 1.0 / (16.0 * std::f64::consts::PI)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn surface_pdf_matches_sampled_density() {
+        let mut s = Object::sphere();
+        s.set_transformation(
+            crate::core::transforms::Transformations::translate(1.0, 0.0, 0.0)
+                * crate::core::transforms::Transformations::scale(1.0, 3.0, 0.5),
+        );
+        for (u, v) in [(0.1, 0.2), (0.5, 0.7), (0.9, 0.4)] {
+            let sample = s.sample_surface(u, v, 0.0);
+            assert!(s.surface_pdf(sample.point, 0.0).approx_eq(sample.pdf));
+        }
+    }
+
+    fn glass_sphere() -> Object {
+        Object::sphere().with_material(Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn precomputing_reflection_vector() {
+        let s = Object::sphere();
+        let r = Ray::new(
+            Point::new(0.0, 2.0, -2.0),
+            Vec3::new(0.0, -1.0, 1.0).normalize(),
+        );
+        let i = Intersection::new(f64::sqrt(2.0) * 2.0 - 1.0, &s);
+        let comps = IntersectionState::new(i, r);
+        let n = comps.normalv;
+
+        assert_eq!(
+            comps.reflectv,
+            r.direction() - n * 2.0 * (r.direction() ^ n)
+        );
+        assert!((comps.reflectv ^ n) > 0.0);
+    }
+
+    #[test]
+    fn finding_n1_and_n2_at_various_intersections() {
+        use crate::core::transforms::Transformations;
+
+        let mut a = glass_sphere();
+        a.set_transformation(Transformations::scale(2.0, 2.0, 2.0));
+        let mut b = glass_sphere();
+        b.set_transformation(Transformations::translate(0.0, 0.0, -0.25));
+        b.set_material(Material {
+            refractive_index: 2.0,
+            ..b.material()
+        });
+        let mut c = glass_sphere();
+        c.set_transformation(Transformations::translate(0.0, 0.0, 0.25));
+        c.set_material(Material {
+            refractive_index: 2.5,
+            ..c.material()
+        });
+
+        let r = Ray::new(Point::new(0.0, 0.0, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(3.25, &c),
+            Intersection::new(4.75, &b),
+            Intersection::new(5.25, &c),
+            Intersection::new(6.0, &a),
+        ]);
+        let expected = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+
+        for (index, (n1, n2)) in expected.iter().enumerate() {
+            let comps = IntersectionState::prepare(xs[index], r, &xs);
+            assert_eq!(comps.n1, *n1);
+            assert_eq!(comps.n2, *n2);
+        }
+    }
+
+    #[test]
+    fn absorption_uses_the_distance_inside_the_medium() {
+        let s = Object::sphere().with_material(Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            absorption: Color::new(0.5, 0.0, 1.0),
+            ..Default::default()
+        });
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(4.0, &s),
+            Intersection::new(6.0, &s),
+        ]);
+
+        // nothing is absorbed on the way to the sphere
+        let entry = IntersectionState::prepare(xs[0], r, &xs);
+        assert_eq!(entry.transmittance, Color::white());
+
+        let exit = IntersectionState::prepare(xs[1], r, &xs);
+        assert_eq!(
+            exit.transmittance,
+            Color::new(f64::exp(-1.0), 1.0, f64::exp(-2.0))
+        );
+
+        // a ray starting inside only crosses part of the medium
+        let r = Ray::new(Point::new(0.0, 0.0, 0.5), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(-1.5, &s),
+            Intersection::new(0.5, &s),
+        ]);
+        let exit = IntersectionState::prepare(xs[1], r, &xs);
+        assert_eq!(exit.transmittance.g, 1.0);
+        assert!(exit.transmittance.b.approx_eq(f64::exp(-0.5)));
+    }
+
+    #[test]
+    fn bounding_sphere_follows_the_transformation() {
+        use crate::core::transforms::Transformations;
+        let mut s = Object::sphere();
+        s.set_transformation(
+            Transformations::translate(1.0, 2.0, 3.0) * Transformations::scale(1.0, 4.0, 2.0),
+        );
+        let (center, radius) = s.bounding_sphere(0.0);
+        assert_eq!(center, Point::new(1.0, 2.0, 3.0));
+        assert!(radius.approx_eq(4.0));
+    }
+
+    #[test]
+    fn dispersive_materials_bend_each_wavelength_differently() {
+        let s = Object::sphere().with_material(Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            dispersion: Dispersion::bk7(),
+            ..Default::default()
+        });
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(4.0, &s),
+            Intersection::new(6.0, &s),
+        ]);
+        let n2 = |r: Ray| IntersectionState::prepare(xs[0], r, &xs).n2;
+
+        assert_eq!(n2(r), 1.5);
+        let blue = n2(r.with_wavelength(Some(450.0)));
+        let red = n2(r.with_wavelength(Some(650.0)));
+        assert!(blue > red && red > 1.5);
+        let state = IntersectionState::prepare(xs[0], r.with_wavelength(Some(450.0)), &xs);
+        assert_eq!(
+            state
+                .spawn_ray(state.under_point, r.direction())
+                .wavelength(),
+            Some(450.0)
+        );
+    }
+
+    #[test]
+    fn frosted_refraction_falls_back_to_the_smooth_direction() {
+        let s = Object::sphere().with_material(Material {
+            roughness: 1.0,
+            ..glass_sphere().material()
+        });
+        // leaving the glass head on, where only some of the facets totally reflect
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(-1.0, &s),
+            Intersection::new(1.0, &s),
+        ]);
+        let state = IntersectionState::prepare(xs[1], r, &xs);
+        let smooth = Vec3::refract(state.eyev, state.normalv, state.n1 / state.n2).unwrap();
+
+        let mut fallbacks = 0;
+        for i in 0..64 {
+            let (u, v) = (i as f64 / 64.0, (i * 37 % 64) as f64 / 64.0);
+            let direction = state.glossy_refraction(u, v).unwrap();
+            assert!((direction ^ state.normalv) < 0.0);
+            if direction == smooth {
+                fallbacks += 1;
+            }
+        }
+        assert!(fallbacks > 0 && fallbacks < 64);
+    }
+
+    #[test]
+    fn under_point_is_below_the_surface() {
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut s = glass_sphere();
+        s.set_transformation(crate::core::transforms::Transformations::translate(
+            0.0, 0.0, 1.0,
+        ));
+        let i = Intersection::new(5.0, &s);
+        let xs = Intersections::from_intersections(vec![i]);
+        let comps = IntersectionState::prepare(i, r, &xs);
+
+        assert!(comps.under_point.z() > SURFACE_EPSILON / 2.0);
+        assert!(comps.point.z() < comps.under_point.z());
+    }
+
+    #[test]
+    fn schlick_under_total_internal_reflection() {
+        let s = glass_sphere();
+        let r = Ray::new(
+            Point::new(0.0, 0.0, f64::sqrt(2.0) / 2.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(-f64::sqrt(2.0) / 2.0, &s),
+            Intersection::new(f64::sqrt(2.0) / 2.0, &s),
+        ]);
+        let comps = IntersectionState::prepare(xs[1], r, &xs);
+
+        assert_eq!(comps.schlick(), 1.0);
+    }
+
+    #[test]
+    fn schlick_with_perpendicular_viewing_angle() {
+        let s = glass_sphere();
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(-1.0, &s),
+            Intersection::new(1.0, &s),
+        ]);
+        let comps = IntersectionState::prepare(xs[1], r, &xs);
+
+        assert!(comps.schlick().approx_eq_low_precision(0.04));
+    }
+
+    #[test]
+    fn schlick_with_small_angle_and_n2_greater_than_n1() {
+        let s = glass_sphere();
+        let r = Ray::new(Point::new(0.0, 0.99, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = Intersections::from_intersections(vec![Intersection::new(1.8589, &s)]);
+        let comps = IntersectionState::prepare(xs[0], r, &xs);
+
+        assert!(comps.schlick().approx_eq_low_precision(0.48873));
+    }
+
     #[test]
     fn precomputing_intersection_states() {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
@@ -327,4 +917,18 @@ mod tests {
         assert_eq!(c.eyev, Vec3::new(0.0, 0.0, -1.0));
         assert_eq!(c.normalv, Vec3::new(0.0, 0.0, -1.0));
     }
+
+    #[test]
+    fn hit_offsets_over_point() {
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut obj = Object::sphere();
+        obj.set_transformation(crate::core::transforms::Transformations::translate(
+            0.0, 0.0, 1.0,
+        ));
+        let i = Intersection::new(5.0, &obj);
+        let c = IntersectionState::new(i, r);
+
+        assert!(c.over_point.z() < -SURFACE_EPSILON / 2.0);
+        assert!(c.point.z() > c.over_point.z());
+    }
 }
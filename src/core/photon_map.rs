@@ -0,0 +1,590 @@
+use std::{cmp::Ordering, collections::BinaryHeap, f64::consts::PI};
+
+use super::{
+    light::Light,
+    object::{IntersectionState, Object},
+    random::Rng,
+    ray::Ray,
+    sampling::{concentric_disk, orthonormal_basis, uniform_sphere},
+    spectrum::{wavelength_to_color, Dispersion, LAMBDA_MAX, LAMBDA_MIN},
+    world::World,
+};
+use crate::primitives::{color::Color, point::Point, tuple::Tuple, vec3::Vec3};
+
+// specular bounces a photon may take before it is dropped
+const MAX_PHOTON_BOUNCES: usize = 8;
+
+// light that reached a diffuse surface at `position` travelling along `direction`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Photon {
+    pub position: Point,
+    pub direction: Vec3,
+    pub power: Color,
+}
+
+// caustic photon map after Jensen, "Realistic Image Synthesis Using Photon Mapping". Photons
+// are shot from the light sources at reflective and transparent objects and stored where they
+// land on a diffuse surface after at least one specular bounce, the light paths that tracing
+// backward from the eye cannot find. Photons sit in a balanced kd-tree laid out in place: the
+// node of a range is its middle element, splitting along `axes[node]`
+#[derive(Clone, PartialEq, Debug)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+    // density estimation gathers up to `neighbours` photons within `max_radius`
+    neighbours: usize,
+    max_radius: f64,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        PhotonMap {
+            photons,
+            axes,
+            neighbours: 64,
+            max_radius: 0.5,
+        }
+    }
+
+    // traces `photons` photons from every light source towards the specular objects of
+    // `world`, `seed` varies the random choices. With dispersive glass in the scene each photon
+    // carries a single wavelength, weighted like the samples of a spectral camera, so the
+    // caustics split into colors
+    pub fn caustics(world: &World, photons: usize, seed: u64) -> Self {
+        let targets: Vec<&Object> = world
+            .objects()
+            .iter()
+            .filter(|o| {
+                let m = o.material();
+                m.reflective > 0.0 || m.transparency > 0.0
+            })
+            .collect();
+        let mut stored = vec![];
+        if targets.is_empty() {
+            return PhotonMap::new(stored);
+        }
+        let spectral = targets
+            .iter()
+            .any(|o| o.material().dispersion != Dispersion::None);
+
+        let per_target = (photons / targets.len()).max(1);
+        let mut stream = 0;
+        for light in world.light_sources() {
+            for target in &targets {
+                let mut rng = Rng::new(seed, stream);
+                stream += 1;
+                for _ in 0..per_target {
+                    let (mut ray, mut power) = emit(light, target, per_target, &mut rng);
+                    if spectral {
+                        let wavelength = LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * rng.next_f64();
+                        ray = ray.with_wavelength(Some(wavelength));
+                        power *= wavelength_to_color(wavelength);
+                    }
+                    trace(world, target, ray, power, &mut rng, &mut stored);
+                }
+            }
+        }
+        PhotonMap::new(stored)
+    }
+
+    pub fn with_gather(mut self, neighbours: usize, max_radius: f64) -> Self {
+        self.neighbours = neighbours.max(1);
+        self.max_radius = max_radius;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    pub fn photons(&self) -> &[Photon] {
+        &self.photons
+    }
+
+    // indices of the `count` photons closest to `point` within `radius`, nearest first
+    pub fn nearest(&self, point: Point, count: usize, radius: f64) -> Vec<usize> {
+        let mut heap = BinaryHeap::new();
+        self.search(
+            0,
+            self.photons.len(),
+            point,
+            count,
+            radius * radius,
+            &mut heap,
+        );
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|c| c.index)
+            .collect()
+    }
+
+    // light reflected towards the eye at `point` by the photons around it, `brdf` gets the
+    // direction the photon came from. Scaled by pi like the point lights in `PathTracer`, so
+    // photons and direct lighting add up
+    pub fn estimate(&self, point: Point, normalv: Vec3, brdf: impl Fn(Vec3) -> Color) -> Color {
+        let nearest = self.nearest(point, self.neighbours, self.max_radius);
+        let Some(&farthest) = nearest.last() else {
+            return Color::black();
+        };
+        let r2 = if nearest.len() == self.neighbours {
+            squared_distance(self.photons[farthest].position, point)
+        } else {
+            self.max_radius * self.max_radius
+        };
+        if r2 <= 0.0 {
+            return Color::black();
+        }
+
+        let mut total = Color::black();
+        for index in nearest {
+            let photon = &self.photons[index];
+            let lightv = -photon.direction;
+            if (lightv ^ normalv) > 0.0 {
+                total += photon.power * brdf(lightv);
+            }
+        }
+        // flux over the area of the gathering disc, times pi
+        total * (1.0 / r2)
+    }
+
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        point: Point,
+        count: usize,
+        max_d2: f64,
+        heap: &mut BinaryHeap<Candidate>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        let axis = self.axes[mid] as usize;
+        let delta = coordinate(point, axis) - coordinate(photon.position, axis);
+        let (near, far) = if delta < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.search(near.0, near.1, point, count, max_d2, heap);
+
+        let d2 = squared_distance(photon.position, point);
+        if d2 <= max_d2 {
+            heap.push(Candidate { d2, index: mid });
+            if heap.len() > count {
+                heap.pop();
+            }
+        }
+
+        // the far side can only help if the splitting plane is within the search radius
+        let limit = match heap.peek() {
+            Some(worst) if heap.len() == count => worst.d2,
+            _ => max_d2,
+        };
+        if delta * delta <= limit {
+            self.search(far.0, far.1, point, count, max_d2, heap);
+        }
+    }
+}
+
+// arranges `photons` into a balanced kd-tree, splitting each range at its middle element along
+// the axis where the range is widest
+fn build(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.is_empty() {
+        return;
+    }
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for p in photons.iter() {
+        for axis in 0..3 {
+            let c = coordinate(p.position, axis);
+            min[axis] = min[axis].min(c);
+            max[axis] = max[axis].max(c);
+        }
+    }
+    let axis = (0..3)
+        .max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b])))
+        .unwrap_or(0);
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| {
+        coordinate(a.position, axis).total_cmp(&coordinate(b.position, axis))
+    });
+    axes[mid] = axis as u8;
+
+    let (left, rest) = photons.split_at_mut(mid);
+    let (left_axes, rest_axes) = axes.split_at_mut(mid);
+    build(left, left_axes);
+    build(&mut rest[1..], &mut rest_axes[1..]);
+}
+
+// a photon leaving `light` towards the bounding sphere of `target`, with the power that makes
+// `count` of them deliver the light's intensity where they first hit
+fn emit(light: &Light, target: &Object, count: usize, rng: &mut Rng) -> (Ray, Color) {
+    let (center, radius) = target.bounding_sphere(0.0);
+    let (u, v) = rng.next_2d();
+
+    let origin = match light {
+        Light::Point(l) => l.position(),
+        Light::Spot(l) => l.position(),
+        Light::Area(l) => {
+            let positions = l.positions(center, rng.next_u32());
+            positions[(rng.next_f64() * positions.len() as f64) as usize % positions.len()]
+        }
+        Light::Directional(l) => {
+            // parallel rays through a disc covering the target, from far enough to be behind
+            // anything in the scene
+            let direction = l.direction().normalize();
+            let (tangent, bitangent) = orthonormal_basis(direction);
+            let (dx, dy) = concentric_disk(u, v);
+            let origin =
+                center + tangent * (dx * radius) + bitangent * (dy * radius) + direction * -1e4;
+            let area = PI * radius * radius;
+            return (
+                Ray::new(origin, direction),
+                l.intensity() * (area / count as f64),
+            );
+        }
+    };
+
+    // uniform over the cone of directions that can reach the bounding sphere
+    let to_center = center - origin;
+    let distance = to_center.magnitude();
+    let (direction, solid_angle) = if distance <= radius {
+        (uniform_sphere(u, v), 4.0 * PI)
+    } else {
+        let cos_max = f64::sqrt(1.0 - (radius / distance).powi(2));
+        let cos_theta = 1.0 - u * (1.0 - cos_max);
+        let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * v;
+        let axis = to_center / distance;
+        let (tangent, bitangent) = orthonormal_basis(axis);
+        let direction = tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + axis * cos_theta;
+        (direction, 2.0 * PI * (1.0 - cos_max))
+    };
+
+    // lights here keep their intensity over distance unless attenuated, so the photon carries
+    // what the light gives at its first hit
+    let ray = Ray::new(origin, direction);
+    let first = first_hit_on(target, &ray).unwrap_or(distance);
+    let sample = light.sample(ray.position(first));
+    let power = sample.intensity
+        * light.attenuation().factor(first)
+        * (first * first * solid_angle / count as f64);
+    (ray, power)
+}
+
+// distance to `target` along `ray`, the photon may still be blocked by something else first
+fn first_hit_on(target: &Object, ray: &Ray) -> Option<f64> {
+    let result = target.intersect(ray);
+    result
+        .i
+        .iter()
+        .map(|i| i.t)
+        .filter(|&t| t > 0.0)
+        .fold(None, |best: Option<f64>, t| {
+            Some(best.map_or(t, |b| b.min(t)))
+        })
+}
+
+// follows a photon through specular bounces, storing it on the first diffuse surface after one.
+// Photons that reach another object before `target` are dropped, the stream of that object
+// already carries the light arriving there
+fn trace(
+    world: &World,
+    target: &Object,
+    mut ray: Ray,
+    mut power: Color,
+    rng: &mut Rng,
+    stored: &mut Vec<Photon>,
+) {
+    for bounce in 0..=MAX_PHOTON_BOUNCES {
+        let xs = world.intersect_world(&ray);
+        let Some(hit) = xs.hit() else {
+            return;
+        };
+        if bounce == 0 && !std::ptr::eq(hit.object, target) {
+            return;
+        }
+        let state = IntersectionState::prepare(*hit, ray, &xs);
+        let m = state.object.material();
+        power *= state.transmittance;
+
+        let (mut mirror, mut refraction) = (m.reflective, m.transparency);
+        if mirror <= 0.0 && refraction <= 0.0 {
+            if bounce > 0 && m.diffuse > 0.0 {
+                stored.push(Photon {
+                    position: state.point,
+                    direction: ray.direction().normalize(),
+                    power,
+                });
+            }
+            return;
+        }
+
+        if mirror > 0.0 && refraction > 0.0 {
+            let reflectance = state.schlick();
+            mirror *= reflectance;
+            refraction *= 1.0 - reflectance;
+        }
+        // bounces are picked with probability equal to their weight, the rest is absorbed
+        let u = rng.next_f64() * (mirror + refraction).max(1.0);
+        let (normal_u, normal_v) = rng.next_2d();
+        let (origin, direction) = if u < mirror {
+            (
+                state.over_point,
+                state.glossy_reflection(normal_u, normal_v),
+            )
+        } else if u < mirror + refraction {
+            match state.glossy_refraction(normal_u, normal_v) {
+                Some(direction) => (state.under_point, direction),
+                None => (state.over_point, state.reflectv),
+            }
+        } else {
+            return;
+        };
+        ray = state.spawn_ray(origin, direction);
+    }
+}
+
+fn coordinate(p: Point, axis: usize) -> f64 {
+    match axis {
+        0 => p.x(),
+        1 => p.y(),
+        _ => p.z(),
+    }
+}
+
+fn squared_distance(a: Point, b: Point) -> f64 {
+    let (dx, dy, dz) = (a.x() - b.x(), a.y() - b.y(), a.z() - b.z());
+    dx * dx + dy * dy + dz * dz
+}
+
+// max-heap entry ordered by squared distance
+#[derive(PartialEq)]
+struct Candidate {
+    d2: f64,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.d2.total_cmp(&other.d2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{light::Material, transforms::Transformations};
+
+    fn scattered_photons(count: u64) -> Vec<Photon> {
+        let mut rng = Rng::new(9, 0);
+        (0..count)
+            .map(|_| Photon {
+                position: Point::new(rng.next_f64(), rng.next_f64(), rng.next_f64()),
+                direction: Vec3::new(0.0, -1.0, 0.0),
+                power: Color::white(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let photons = scattered_photons(500);
+        let map = PhotonMap::new(photons.clone());
+        let point = Point::new(0.4, 0.6, 0.5);
+
+        let mut expected: Vec<f64> = photons
+            .iter()
+            .map(|p| squared_distance(p.position, point))
+            .collect();
+        expected.sort_by(|a, b| a.total_cmp(b));
+
+        let found: Vec<f64> = map
+            .nearest(point, 10, 1.0)
+            .into_iter()
+            .map(|i| squared_distance(map.photons()[i].position, point))
+            .collect();
+        assert_eq!(found, expected[..10].to_vec());
+    }
+
+    #[test]
+    fn nearest_stays_within_the_radius() {
+        let map = PhotonMap::new(scattered_photons(200));
+        let point = Point::new(0.5, 0.5, 0.5);
+        let found = map.nearest(point, 1000, 0.2);
+        assert!(!found.is_empty() && found.len() < 200);
+        for i in found {
+            assert!(squared_distance(map.photons()[i].position, point) <= 0.04);
+        }
+        assert!(map.nearest(Point::new(5.0, 5.0, 5.0), 10, 1.0).is_empty());
+    }
+
+    #[test]
+    fn estimate_recovers_the_photon_density() {
+        // one photon of power 0.01 per 0.1 x 0.1 cell is an irradiance of 1
+        let mut photons = vec![];
+        for i in 0..40 {
+            for j in 0..40 {
+                photons.push(Photon {
+                    position: Point::new(i as f64 * 0.1 - 2.0, 0.0, j as f64 * 0.1 - 2.0),
+                    direction: Vec3::new(0.0, -1.0, 0.0),
+                    power: Color::new(0.01, 0.01, 0.01),
+                });
+            }
+        }
+        let map = PhotonMap::new(photons).with_gather(100, 1.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let white = |_| Color::white() * (1.0 / PI);
+
+        let c = map.estimate(Point::new(0.05, 0.0, 0.05), up, white);
+        assert!((c.r - 1.0).abs() < 0.15, "{c:?}");
+        // photons arriving from behind the surface don't count
+        let below = map.estimate(Point::new(0.05, 0.0, 0.05), -up, white);
+        assert_eq!(below, Color::black());
+    }
+
+    #[test]
+    fn glass_sphere_focuses_light_below_it() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        let mut ground = Object::sphere();
+        ground.set_transformation(
+            Transformations::translate(0.0, -101.0, 0.0)
+                * Transformations::scale(100.0, 100.0, 100.0),
+        );
+        w.push_object(ground);
+        let mut glass = Object::sphere().with_material(Material {
+            diffuse: 0.0,
+            specular: 0.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        });
+        glass.set_transformation(Transformations::translate(0.0, 1.0, 0.0));
+        w.push_object(glass);
+
+        // along the ground into the shadow of the glass
+        let r = Ray::new(Point::new(4.0, -0.5, 0.0), Vec3::new(-4.0, -0.5, 0.0));
+        let shadowed = w.color_at(r);
+
+        let caustics = PhotonMap::caustics(&w, 20000, 1).with_gather(50, 0.3);
+        assert!(!caustics.is_empty());
+        // everything went through the glass and lands on the ground, mostly right beneath it
+        let beneath = caustics
+            .photons()
+            .iter()
+            .inspect(|p| assert!(p.position.y() < -0.9))
+            .filter(|p| p.position.x().abs() < 1.0 && p.position.z().abs() < 1.0)
+            .count();
+        assert!(beneath as f64 > 0.8 * caustics.len() as f64);
+        w.set_caustics(caustics);
+        let focused = w.color_at(r);
+        assert!(focused.r > shadowed.r + 0.5, "{shadowed:?} {focused:?}");
+    }
+
+    #[test]
+    fn dispersive_glass_splits_caustic_photons_into_colors() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        let mut ground = Object::sphere();
+        ground.set_transformation(
+            Transformations::translate(0.0, -101.0, 0.0)
+                * Transformations::scale(100.0, 100.0, 100.0),
+        );
+        w.push_object(ground);
+        let mut prism = Object::sphere().with_material(Material {
+            diffuse: 0.0,
+            specular: 0.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            dispersion: Dispersion::bk7(),
+            ..Default::default()
+        });
+        prism.set_transformation(Transformations::translate(0.0, 1.0, 0.0));
+        w.push_object(prism);
+
+        let caustics = PhotonMap::caustics(&w, 2000, 1);
+        assert!(!caustics.is_empty());
+        let reddish = caustics.photons().iter().filter(|p| p.power.r > p.power.b);
+        let bluish = caustics.photons().iter().filter(|p| p.power.b > p.power.r);
+        assert!(reddish.count() > 0 && bluish.count() > 0);
+    }
+
+    // a glass sphere of index 1 bends nothing, the light it lets through must match the light
+    // reaching the ground without it
+    fn caustic_over_direct(spheres: &[f64]) -> f64 {
+        let light = Light::from(
+            crate::core::light::PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white())
+                .with_attenuation(crate::core::light::Attenuation::inverse_square()),
+        );
+        let ground = || {
+            let mut ground = Object::sphere().with_material(Material {
+                ambient: 0.0,
+                specular: 0.0,
+                ..Default::default()
+            });
+            ground.set_transformation(
+                Transformations::translate(0.0, -101.0, 0.0)
+                    * Transformations::scale(100.0, 100.0, 100.0),
+            );
+            ground
+        };
+        let r = Ray::new(Point::new(4.0, 0.0, 0.0), Vec3::new(-4.0, -1.0, 0.0));
+
+        let mut open = World::new();
+        open.push_light_source(light);
+        open.push_object(ground());
+        let direct = open.color_at(r);
+
+        let mut w = World::new();
+        w.push_light_source(light);
+        w.push_object(ground());
+        for &y in spheres {
+            let mut glass = Object::sphere().with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                refractive_index: 1.0,
+                ..Default::default()
+            });
+            glass.set_transformation(Transformations::translate(0.0, y, 0.0));
+            w.push_object(glass);
+        }
+        w.set_caustics(PhotonMap::caustics(&w, 40000, 3).with_gather(400, 0.5));
+        w.color_at(r).r / direct.r
+    }
+
+    #[test]
+    fn caustics_through_clear_glass_match_the_direct_light() {
+        let ratio = caustic_over_direct(&[1.0]);
+        assert!((ratio - 1.0).abs() < 0.1, "{ratio}");
+    }
+
+    #[test]
+    fn overlapping_glass_does_not_count_the_light_twice() {
+        let ratio = caustic_over_direct(&[1.0, 3.5]);
+        assert!((ratio - 1.0).abs() < 0.1, "{ratio}");
+    }
+}
@@ -0,0 +1,105 @@
+// PCG32 (XSH RR variant) from O'Neill, "PCG: A Family of Simple Fast Space-Efficient
+// Statistically Good Algorithms for Random Number Generation". Every camera sample gets its
+// own generator derived from the pixel and sample index, so the image does not depend on the
+// order in which pixels are rendered or on which thread renders them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Rng {
+    // generators with different `stream`s produce unrelated sequences for the same `seed`
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Rng {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    // generator for sample `sample` of pixel (px, py), `seed` varies the whole image
+    pub fn for_sample(px: usize, py: usize, sample: usize, seed: u64) -> Self {
+        let pixel = ((py as u64) << 32) | px as u64;
+        Rng::new(splitmix64(seed ^ splitmix64(pixel)), sample as u64)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        self.next_u32() as f64 / 4294967296.0
+    }
+
+    pub fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_f64(), self.next_f64())
+    }
+}
+
+// finalizer of the SplitMix64 generator, spreads nearby inputs over all 64 bits
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_implementation() {
+        // first outputs of pcg32-demo with the fixed seed (42, 54)
+        let mut rng = Rng::new(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for e in expected {
+            assert_eq!(rng.next_u32(), e);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_sequence() {
+        let mut a = Rng::for_sample(3, 7, 2, 99);
+        let mut b = Rng::for_sample(3, 7, 2, 99);
+        for _ in 0..16 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn neighbouring_samples_are_unrelated() {
+        let first = |px, py, sample, seed| Rng::for_sample(px, py, sample, seed).next_u32();
+        let base = first(3, 7, 2, 99);
+        assert_ne!(base, first(4, 7, 2, 99));
+        assert_ne!(base, first(3, 8, 2, 99));
+        assert_ne!(base, first(3, 7, 3, 99));
+        assert_ne!(base, first(3, 7, 2, 100));
+        assert_ne!(first(7, 3, 2, 99), first(3, 7, 2, 99));
+    }
+
+    #[test]
+    fn floats_are_uniform_in_unit_interval() {
+        let mut rng = Rng::new(1, 0);
+        let mut mean = 0.0;
+        for _ in 0..4096 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            mean += x / 4096.0;
+        }
+        assert!((mean - 0.5).abs() < 0.02);
+    }
+}
@@ -5,11 +5,50 @@ use crate::primitives::{point::Point, tuple::Tuple, vec3::Vec3};
 pub struct Ray {
     origin: Point,
     direction: Vec3,
+    // instant within the shutter interval the ray was sent at, drives moving objects
+    time: f64,
+    // nanometers, carried by the rays of spectral renders and picked up by dispersive materials
+    wavelength: Option<f64>,
+    // drawn from the camera sample, varies the random choices made while shading the ray
+    seed: u32,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+            wavelength: None,
+            seed: 0,
+        }
+    }
+
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
     }
 
     pub fn position(&self, t: f64) -> Point {
@@ -30,6 +69,9 @@ impl Transform for Ray {
         return Ray {
             origin: (*transform) * self.origin,
             direction: (*transform) * self.direction,
+            time: self.time,
+            wavelength: self.wavelength,
+            seed: self.seed,
         };
     }
 }
@@ -57,6 +99,31 @@ mod tests {
         assert_eq!(r.position(-1.0), Point::new(1.0, 3.0, 4.0));
     }
 
+    #[test]
+    fn ray_time_survives_transformation() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0)).with_time(0.25);
+        assert_eq!(Ray::new(r.origin(), r.direction()).time(), 0.0);
+        assert_eq!(r.translate(3.0, 4.0, 5.0).transform().time(), 0.25);
+    }
+
+    #[test]
+    fn ray_wavelength_survives_transformation() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(r.wavelength(), None);
+        let r = r.with_wavelength(Some(550.0));
+        assert_eq!(r.scale(2.0, 2.0, 2.0).transform().wavelength(), Some(550.0));
+    }
+
+    #[test]
+    fn ray_seed_survives_transformation() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(r.seed(), 0);
+        assert_eq!(
+            r.with_seed(7).translate(1.0, 0.0, 0.0).transform().seed(),
+            7
+        );
+    }
+
     #[test]
     fn ray_transformable() {
         {
@@ -0,0 +1,532 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::primitives::{tuple::Tuple, vec3::Vec3};
+
+// where the samples of a pixel land inside the unit square
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplePattern {
+    Grid,
+    Jittered,
+    Halton,
+    Hammersley,
+}
+
+impl SamplePattern {
+    // `index`-th of `count` points in [0, 1)^2, `seed` decorrelates neighbouring pixels
+    pub fn point(&self, index: usize, count: usize, seed: u32) -> (f64, f64) {
+        match self {
+            SamplePattern::Grid => grid_point(index, count, (0.5, 0.5)),
+            SamplePattern::Jittered => {
+                let jitter = (
+                    hash_to_unit(seed ^ hash(2 * index as u32)),
+                    hash_to_unit(seed ^ hash(2 * index as u32 + 1)),
+                );
+                grid_point(index, count, jitter)
+            }
+            SamplePattern::Halton => rotate(
+                (radical_inverse(2, index + 1), radical_inverse(3, index + 1)),
+                seed,
+            ),
+            SamplePattern::Hammersley => rotate(
+                (
+                    (index as f64 + 0.5) / count as f64,
+                    radical_inverse(2, index),
+                ),
+                seed,
+            ),
+        }
+    }
+}
+
+// splits the unit square into roughly square cells, one per sample
+fn grid_point(index: usize, count: usize, offset: (f64, f64)) -> (f64, f64) {
+    let cols = f64::ceil(f64::sqrt(count as f64)) as usize;
+    let rows = count.div_ceil(cols);
+    let (col, row) = (index % cols, index / cols);
+    (
+        (col as f64 + offset.0) / cols as f64,
+        (row as f64 + offset.1) / rows as f64,
+    )
+}
+
+// Cranley-Patterson rotation, keeps the sequence stratified while shifting it per pixel
+fn rotate(p: (f64, f64), seed: u32) -> (f64, f64) {
+    if seed == 0 {
+        return p;
+    }
+    let shift = (hash_to_unit(seed), hash_to_unit(hash(seed)));
+    ((p.0 + shift.0).fract(), (p.1 + shift.1).fract())
+}
+
+pub fn radical_inverse(base: usize, mut index: usize) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * factor;
+        index /= base;
+        factor *= inv_base;
+    }
+    result
+}
+
+// PCG output permutation used as an integer hash
+pub fn hash(x: u32) -> u32 {
+    let state = x.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+pub fn hash_to_unit(x: u32) -> f64 {
+    hash(x) as f64 / 4294967296.0
+}
+
+// uniform value for an extra sample dimension (lens, shutter time, ...), uncorrelated with the
+// pixel pattern so that film and lens positions don't line up
+pub fn random_1d(seed: u32, index: usize, dimension: u32) -> f64 {
+    hash_to_unit(hash(seed ^ hash(dimension)) ^ index as u32)
+}
+
+pub fn random_2d(seed: u32, index: usize, dimension: u32) -> (f64, f64) {
+    (
+        random_1d(seed, index, dimension),
+        random_1d(seed, index, dimension.wrapping_add(1)),
+    )
+}
+
+// Shirley-Chiu concentric mapping from the unit square onto the unit disk
+pub fn concentric_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// two unit vectors completing `n` (unit length) to an orthonormal basis
+pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let helper = if n.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = (helper * n).normalize();
+    (tangent, n * tangent)
+}
+
+// direction in the hemisphere around `normal` with density cos(theta) / pi, Malley's method
+pub fn cosine_hemisphere(normal: Vec3, u: f64, v: f64) -> Vec3 {
+    let (x, y) = concentric_disk(u, v);
+    let z = f64::sqrt(f64::max(0.0, 1.0 - x * x - y * y));
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * x + bitangent * y + normal * z).normalize()
+}
+
+// direction uniformly distributed over the unit sphere, density 1 / (4 pi)
+pub fn uniform_sphere(u: f64, v: f64) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// direction uniformly distributed over the hemisphere around `normal`, density 1 / (2 pi)
+pub fn uniform_hemisphere(normal: Vec3, u: f64, v: f64) -> Vec3 {
+    let d = uniform_sphere(u, v);
+    if (d ^ normal) < 0.0 {
+        -d
+    } else {
+        d
+    }
+}
+
+// barycentric coordinates (b0, b1) of a point uniformly distributed over a triangle, the third
+// coordinate is 1 - b0 - b1
+pub fn uniform_triangle(u: f64, v: f64) -> (f64, f64) {
+    let su = u.sqrt();
+    (1.0 - su, v * su)
+}
+
+// reconstruction filter, weights a sample by its offset (in pixels) from the pixel center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent { radius: f64 },
+    Gaussian { radius: f64, alpha: f64 },
+    Mitchell { b: f64, c: f64 },
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent { radius } | Filter::Gaussian { radius, .. } => *radius,
+            Filter::Mitchell { .. } => 2.0,
+        }
+    }
+
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent { radius } => f64::max(0.0, radius - x),
+            Filter::Gaussian { radius, alpha } => f64::max(
+                0.0,
+                f64::exp(-alpha * x * x) - f64::exp(-alpha * radius * radius),
+            ),
+            Filter::Mitchell { b, c } => {
+                let (x2, x3) = (x * x, x * x * x);
+                let w = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x3
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x2
+                        + (6.0 - 2.0 * b)
+                } else if x < 2.0 {
+                    (-b - 6.0 * c) * x3
+                        + (6.0 * b + 30.0 * c) * x2
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                } else {
+                    0.0
+                };
+                w / 6.0
+            }
+        }
+    }
+}
+
+// piecewise-constant density over [0, 1) proportional to `func`, sampled by inverting its CDF.
+// A function that is zero everywhere, or has no values at all, gives the uniform density
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(mut func: Vec<f64>) -> Self {
+        if func.is_empty() {
+            func.push(0.0);
+        }
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if integral == 0.0 {
+                i as f64 / n as f64
+            } else {
+                *c / integral
+            };
+        }
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // (x in [0, 1), density at x, bucket x falls in)
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = (offset as f64 + du) / self.len() as f64;
+        (x, self.pdf_at(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        self.pdf_at(offset)
+    }
+
+    fn pdf_at(&self, offset: usize) -> f64 {
+        if self.integral == 0.0 {
+            1.0
+        } else {
+            self.func[offset].abs() / self.integral
+        }
+    }
+}
+
+// density over [0, 1)^2 from a row-major grid of `func` values, rows are sampled first. An empty
+// grid, or one with fewer than `width * height` values, gives the uniform density
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        if width == 0 || height == 0 || func.len() < width * height {
+            return Distribution2D::new(&[0.0], 1, 1);
+        }
+        let conditional: Vec<Distribution1D> = (0..height)
+            .map(|row| Distribution1D::new(func[row * width..(row + 1) * width].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // ((u, v), density at (u, v))
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_row, row) = self.marginal.sample_continuous(u1);
+        let (u, pdf_column, _) = self.conditional[row].sample_continuous(u0);
+        ((u, v), pdf_row * pdf_column)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        let marginal = self.marginal.integral();
+        if marginal == 0.0 {
+            return 1.0;
+        }
+        self.conditional[row].pdf(u) * self.conditional[row].integral() / marginal
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Supersampling {
+    pub samples: usize,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+}
+
+impl Default for Supersampling {
+    fn default() -> Self {
+        Supersampling {
+            samples: 1,
+            pattern: SamplePattern::Grid,
+            filter: Filter::Box,
+        }
+    }
+}
+
+impl Supersampling {
+    pub fn new(samples: usize, pattern: SamplePattern, filter: Filter) -> Self {
+        Supersampling {
+            samples: samples.max(1),
+            pattern,
+            filter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::random::Rng, primitives::float::ApproxEq};
+
+    #[test]
+    fn single_grid_sample_is_pixel_center() {
+        assert_eq!(SamplePattern::Grid.point(0, 1, 0), (0.5, 0.5));
+    }
+
+    #[test]
+    fn grid_samples_are_cell_centers() {
+        let points: Vec<_> = (0..4).map(|i| SamplePattern::Grid.point(i, 4, 0)).collect();
+        assert_eq!(
+            points,
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells() {
+        for i in 0..9 {
+            let (x, y) = SamplePattern::Jittered.point(i, 9, 42);
+            let (col, row) = ((i % 3) as f64, (i / 3) as f64);
+            assert!(x >= col / 3.0 && x < (col + 1.0) / 3.0);
+            assert!(y >= row / 3.0 && y < (row + 1.0) / 3.0);
+        }
+    }
+
+    #[test]
+    fn radical_inverse_mirrors_digits() {
+        assert!(radical_inverse(2, 1).approx_eq(0.5));
+        assert!(radical_inverse(2, 3).approx_eq(0.75));
+        assert!(radical_inverse(3, 1).approx_eq(1.0 / 3.0));
+        assert!(radical_inverse(3, 4).approx_eq(4.0 / 9.0));
+    }
+
+    #[test]
+    fn low_discrepancy_samples_are_in_unit_square() {
+        for pattern in [SamplePattern::Halton, SamplePattern::Hammersley] {
+            for i in 0..16 {
+                let (x, y) = pattern.point(i, 16, 7);
+                assert!((0.0..1.0).contains(&x));
+                assert!((0.0..1.0).contains(&y));
+            }
+        }
+    }
+
+    #[test]
+    fn filters_peak_at_center_and_vanish_at_radius() {
+        let filters = [
+            Filter::Tent { radius: 1.0 },
+            Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            Filter::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+        ];
+        for f in filters {
+            assert!(f.weight(0.0, 0.0) > f.weight(0.25, 0.0));
+            assert!(f.weight(f.radius(), 0.0).approx_eq(0.0));
+        }
+        assert_eq!(Filter::Box.weight(0.4, -0.3), 1.0);
+    }
+
+    #[test]
+    fn distribution_1d_follows_its_function() {
+        let d = Distribution1D::new(vec![1.0, 3.0]);
+
+        assert!(d.integral().approx_eq(2.0));
+        assert!(d.pdf(0.25).approx_eq(0.5));
+        assert!(d.pdf(0.75).approx_eq(1.5));
+
+        let (x, pdf, offset) = d.sample_continuous(0.5);
+        assert_eq!(offset, 1);
+        assert!(x.approx_eq(0.5 + 1.0 / 6.0));
+        assert!(pdf.approx_eq(1.5));
+        assert_eq!(d.sample_continuous(0.1).2, 0);
+    }
+
+    #[test]
+    fn distribution_1d_of_zeros_is_uniform() {
+        let d = Distribution1D::new(vec![0.0; 4]);
+        let (x, pdf, _) = d.sample_continuous(0.6);
+        assert!(x.approx_eq(0.6));
+        assert_eq!(pdf, 1.0);
+    }
+
+    #[test]
+    fn empty_distributions_are_uniform() {
+        let d = Distribution1D::new(vec![]);
+        assert_eq!(d.len(), 1);
+        let (x, pdf, _) = d.sample_continuous(0.3);
+        assert!(x.approx_eq(0.3));
+        assert_eq!(pdf, 1.0);
+        assert_eq!(d.pdf(0.9), 1.0);
+
+        for d in [
+            Distribution2D::new(&[], 0, 4),
+            Distribution2D::new(&[1.0, 2.0], 2, 2),
+        ] {
+            let ((u, v), pdf) = d.sample(0.25, 0.75);
+            assert!(u.approx_eq(0.25) && v.approx_eq(0.75));
+            assert_eq!(pdf, 1.0);
+            assert_eq!(d.pdf(u, v), 1.0);
+        }
+    }
+
+    #[test]
+    fn distribution_2d_concentrates_on_bright_cells() {
+        let d = Distribution2D::new(&[0.0, 0.0, 0.0, 4.0], 2, 2);
+        for i in 0..16 {
+            let ((u, v), pdf) = d.sample(random_1d(1, i, 0), random_1d(1, i, 1));
+            assert!(u >= 0.5 && v >= 0.5);
+            assert!(pdf.approx_eq(4.0));
+            assert!(d.pdf(u, v).approx_eq(4.0));
+        }
+        assert_eq!(d.pdf(0.25, 0.25), 0.0);
+    }
+
+    #[test]
+    fn cosine_hemisphere_stays_above_the_surface() {
+        let normal = Vec3::new(1.0, 2.0, -0.5).normalize();
+        let mut mean_cos = 0.0;
+        for i in 0..256 {
+            let (u, v) = random_2d(5, i, 0);
+            let d = cosine_hemisphere(normal, u, v);
+            assert!(d.magnitude().approx_eq(1.0));
+            assert!((d ^ normal) >= 0.0);
+            mean_cos += (d ^ normal) / 256.0;
+        }
+        // E[cos] under cos / pi is 2 / 3
+        assert!(mean_cos.approx_eq_epsilon(2.0 / 3.0, 0.03));
+    }
+
+    #[test]
+    fn uniform_sphere_and_hemisphere_are_balanced() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let mut rng = Rng::new(11, 0);
+        let (mut mean, mut mean_cos) = (Vec3::new(0.0, 0.0, 0.0), 0.0);
+        for _ in 0..2048 {
+            let (u, v) = rng.next_2d();
+            let d = uniform_sphere(u, v);
+            assert!(d.magnitude().approx_eq(1.0));
+            mean = mean + d * (1.0 / 2048.0);
+
+            let h = uniform_hemisphere(normal, u, v);
+            assert!((h ^ normal) >= 0.0);
+            mean_cos += (h ^ normal) / 2048.0;
+        }
+        assert!(mean.magnitude() < 0.05);
+        // E[cos] under the uniform hemisphere density is 1 / 2
+        assert!(mean_cos.approx_eq_epsilon(0.5, 0.02));
+    }
+
+    #[test]
+    fn uniform_triangle_centroid_is_a_third() {
+        let mut rng = Rng::new(12, 0);
+        let (mut m0, mut m1) = (0.0, 0.0);
+        for _ in 0..2048 {
+            let (u, v) = rng.next_2d();
+            let (b0, b1) = uniform_triangle(u, v);
+            assert!(b0 >= 0.0 && b1 >= 0.0 && b0 + b1 <= 1.0);
+            m0 += b0 / 2048.0;
+            m1 += b1 / 2048.0;
+        }
+        assert!(m0.approx_eq_epsilon(1.0 / 3.0, 0.02) && m1.approx_eq_epsilon(1.0 / 3.0, 0.02));
+    }
+
+    #[test]
+    fn concentric_disk_maps_center_and_edges() {
+        assert_eq!(concentric_disk(0.5, 0.5), (0.0, 0.0));
+        let (x, y) = concentric_disk(1.0, 0.5);
+        assert!(x.approx_eq(1.0) && y.approx_eq(0.0));
+        for i in 0..64 {
+            let (x, y) = concentric_disk(random_1d(3, i, 0), random_1d(3, i, 1));
+            assert!(x * x + y * y <= 1.0 + 1e-9);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::f64::consts::PI;
+
+use super::sampling::{cosine_hemisphere, orthonormal_basis};
+use crate::primitives::{color::Color, vec3::Vec3};
+
+// reflectance model evaluated by `Material::brdf` and `lighting`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingModel {
+    // the book's model, driven by `diffuse`, `specular` and `shininess`. The diffuse term and
+    // the highlight can be swapped independently
+    Phong {
+        diffuse: DiffuseModel,
+        specular: SpecularModel,
+    },
+    // Cook-Torrance with a GGX distribution, Smith masking and Schlick Fresnel. `color` is
+    // the base color, `diffuse`, `specular` and `shininess` are ignored. Metals tint their
+    // reflection with the base color and have no diffuse part, roughness goes from 0 (mirror
+    // like) to 1.
+    Microfacet {
+        metallic: f64,
+        roughness: f64,
+    },
+}
+
+impl Default for ShadingModel {
+    fn default() -> Self {
+        ShadingModel::Phong {
+            diffuse: DiffuseModel::Lambert,
+            specular: SpecularModel::Phong,
+        }
+    }
+}
+
+// diffuse term of the Phong family
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DiffuseModel {
+    #[default]
+    Lambert,
+    // Oren-Nayar rough diffuse, `sigma` is the standard deviation of the facet slopes in
+    // radians and 0 gives back Lambert
+    OrenNayar {
+        sigma: f64,
+    },
+}
+
+// highlight of the Phong family
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpecularModel {
+    // reflect-vector highlight
+    #[default]
+    Phong,
+    // half-vector highlight, which needs roughly four times the Phong `shininess` for a
+    // highlight of the same size
+    BlinnPhong,
+}
+
+// Oren-Nayar qualitative model, as a factor on the Lambertian term
+pub fn oren_nayar(sigma: f64, normalv: Vec3, eyev: Vec3, lightv: Vec3) -> f64 {
+    let s2 = sigma * sigma;
+    let a = 1.0 - 0.5 * s2 / (s2 + 0.33);
+    let b = 0.45 * s2 / (s2 + 0.09);
+
+    let cos_i = (normalv ^ lightv).clamp(-1.0, 1.0);
+    let cos_r = (normalv ^ eyev).clamp(-1.0, 1.0);
+    // cosine of the azimuth between the directions, projected on the tangent plane
+    let light_tangent = lightv - normalv * cos_i;
+    let eye_tangent = eyev - normalv * cos_r;
+    let lengths = light_tangent.magnitude() * eye_tangent.magnitude();
+    let cos_phi = if lengths > 1.0e-9 {
+        (light_tangent ^ eye_tangent) / lengths
+    } else {
+        0.0
+    };
+
+    let (theta_i, theta_r) = (cos_i.acos(), cos_r.acos());
+    let (alpha, beta) = (theta_i.max(theta_r), theta_i.min(theta_r));
+    a + b * cos_phi.max(0.0) * alpha.sin() * beta.tan()
+}
+
+// Blinn-Phong highlight (n . h)^shininess
+pub fn blinn_phong(normalv: Vec3, eyev: Vec3, lightv: Vec3, shininess: f64) -> f64 {
+    let h = (eyev + lightv).normalize();
+    f64::max(0.0, normalv ^ h).powf(shininess)
+}
+
+// roughness is remapped to alpha = roughness^2, clamped away from the singular mirror case
+fn alpha(roughness: f64) -> f64 {
+    (roughness * roughness).clamp(1.0e-3, 1.0)
+}
+
+// GGX (Trowbridge-Reitz) normal distribution
+pub fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+    if n_dot_h <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// Smith masking for one direction under GGX
+pub fn smith_g1(n_dot_v: f64, alpha: f64) -> f64 {
+    if n_dot_v <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + f64::sqrt(a2 + (1.0 - a2) * n_dot_v * n_dot_v))
+}
+
+pub fn fresnel_schlick(f0: Color, cos: f64) -> Color {
+    let k = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::white() - f0) * k
+}
+
+// reflectance at normal incidence, 4% for dielectrics and the base color for metals
+fn base_reflectance(color: Color, metallic: f64) -> Color {
+    Color::new(0.04, 0.04, 0.04) * (1.0 - metallic) + color * metallic
+}
+
+pub fn microfacet_brdf(
+    color: Color,
+    metallic: f64,
+    roughness: f64,
+    normalv: Vec3,
+    eyev: Vec3,
+    lightv: Vec3,
+) -> Color {
+    let n_dot_l = normalv ^ lightv;
+    let n_dot_v = normalv ^ eyev;
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return Color::black();
+    }
+    let a = alpha(roughness);
+    let h = (eyev + lightv).normalize();
+    let fresnel = fresnel_schlick(base_reflectance(color, metallic), eyev ^ h);
+
+    let specular = fresnel
+        * (ggx_distribution(normalv ^ h, a) * smith_g1(n_dot_l, a) * smith_g1(n_dot_v, a)
+            / (4.0 * n_dot_l * n_dot_v));
+    // whatever the surface doesn't reflect enters it, metals absorb it
+    let diffuse = (Color::white() - fresnel) * color * ((1.0 - metallic) / PI);
+    diffuse + specular
+}
+
+// chance of sampling the GGX lobe instead of the cosine-weighted diffuse lobe
+fn specular_probability(metallic: f64) -> f64 {
+    0.5 + 0.5 * metallic.clamp(0.0, 1.0)
+}
+
+// direction toward the light importance sampled from the microfacet lobes, `choice` picks
+// the lobe and (u, v) the direction within it
+pub fn sample_microfacet(
+    metallic: f64,
+    roughness: f64,
+    normalv: Vec3,
+    eyev: Vec3,
+    choice: f64,
+    u: f64,
+    v: f64,
+) -> Vec3 {
+    if choice >= specular_probability(metallic) {
+        return cosine_hemisphere(normalv, u, v);
+    }
+    let h = sample_ggx_normal(normalv, roughness, u, v);
+    Vec3::reflect(-eyev, h)
+}
+
+// microfacet normal around `normalv` distributed as D(h) * cos(theta_h)
+pub fn sample_ggx_normal(normalv: Vec3, roughness: f64, u: f64, v: f64) -> Vec3 {
+    let a = alpha(roughness);
+    let cos_theta = f64::sqrt((1.0 - u) / (1.0 + (a * a - 1.0) * u));
+    let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * v;
+    let (tangent, bitangent) = orthonormal_basis(normalv);
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normalv * cos_theta
+}
+
+// solid angle density of `sample_microfacet`
+pub fn microfacet_pdf(
+    metallic: f64,
+    roughness: f64,
+    normalv: Vec3,
+    eyev: Vec3,
+    lightv: Vec3,
+) -> f64 {
+    let n_dot_l = normalv ^ lightv;
+    if n_dot_l <= 0.0 {
+        return 0.0;
+    }
+    let h = (eyev + lightv).normalize();
+    let v_dot_h = eyev ^ h;
+    let specular = if v_dot_h > 0.0 {
+        let a = alpha(roughness);
+        ggx_distribution(normalv ^ h, a) * (normalv ^ h) / (4.0 * v_dot_h)
+    } else {
+        0.0
+    };
+    let p = specular_probability(metallic);
+    p * specular + (1.0 - p) * n_dot_l / PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{random::Rng, sampling::uniform_sphere},
+        primitives::{float::ApproxEq, tuple::Tuple},
+    };
+
+    #[test]
+    fn ggx_distribution_is_normalized() {
+        // the projected microfacet area integrates to one over the hemisphere
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let mut rng = Rng::new(1, 0);
+        for roughness in [0.3, 0.6, 1.0] {
+            let a = alpha(roughness);
+            let mut total = 0.0;
+            let count = 200_000;
+            for _ in 0..count {
+                let (u, v) = rng.next_2d();
+                let h = uniform_sphere(u, v);
+                total += ggx_distribution(h ^ n, a) * (h ^ n).max(0.0) * 4.0 * PI;
+            }
+            assert!((total / count as f64).approx_eq_epsilon(1.0, 0.05));
+        }
+    }
+
+    #[test]
+    fn microfacet_pdf_integrates_to_one() {
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let eyev = Vec3::new(0.5, 0.0, 1.0).normalize();
+        let mut rng = Rng::new(2, 0);
+        let count = 200_000;
+        let mut total = 0.0;
+        for _ in 0..count {
+            let (u, v) = rng.next_2d();
+            total += microfacet_pdf(0.5, 0.5, n, eyev, uniform_sphere(u, v)) * 4.0 * PI;
+        }
+        // reflected half-vector samples that fall below the surface are lost
+        let integral = total / count as f64;
+        assert!(integral <= 1.02 && integral > 0.9);
+    }
+
+    #[test]
+    fn oren_nayar_without_roughness_is_lambert() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let eyev = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let lightv = Vec3::new(-0.3, 1.0, 0.5).normalize();
+        assert!(oren_nayar(0.0, n, eyev, lightv).approx_eq(1.0));
+    }
+
+    #[test]
+    fn oren_nayar_flattens_the_falloff() {
+        // rough surfaces look darker head-on and brighter back-scattering at grazing angles
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let head_on = oren_nayar(0.5, n, n, n);
+        let grazing = Vec3::new(1.0, 0.2, 0.0).normalize();
+        let retro = oren_nayar(0.5, n, grazing, grazing);
+        assert!(head_on < 1.0 && retro > head_on);
+    }
+
+    #[test]
+    fn blinn_phong_peaks_at_the_half_vector() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let lightv = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let mirror = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        assert!(blinn_phong(n, mirror, lightv, 50.0).approx_eq(1.0));
+        assert!(blinn_phong(n, Vec3::new(-1.0, 2.0, 0.0).normalize(), lightv, 50.0) < 1.0);
+    }
+
+    #[test]
+    fn microfacet_brdf_is_reciprocal() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let a = Vec3::new(0.3, 1.0, 0.2).normalize();
+        let b = Vec3::new(-0.6, 0.5, 0.1).normalize();
+        let color = Color::new(0.9, 0.5, 0.2);
+        assert_eq!(
+            microfacet_brdf(color, 0.3, 0.4, n, a, b),
+            microfacet_brdf(color, 0.3, 0.4, n, b, a)
+        );
+    }
+
+    #[test]
+    fn metals_reflect_their_color_without_diffuse() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let color = Color::new(1.0, 0.5, 0.0);
+        let eyev = Vec3::new(1.0, 1.0, 0.0).normalize();
+        // far from the highlight only the diffuse part of a dielectric remains
+        let grazing = Vec3::new(-0.2, 1.0, 0.9).normalize();
+        let metal = microfacet_brdf(color, 1.0, 0.2, n, eyev, grazing);
+        let plastic = microfacet_brdf(color, 0.0, 0.2, n, eyev, grazing);
+        assert!(metal.r < 0.01 && plastic.r > 0.25);
+
+        let mirror = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        let highlight = microfacet_brdf(color, 1.0, 0.2, n, eyev, mirror);
+        assert!(highlight.r > highlight.g && highlight.b < 0.01 * highlight.r);
+    }
+
+    #[test]
+    fn rough_metal_does_not_create_energy() {
+        // white furnace: albedo = E[brdf * cos / pdf] stays below one, single scattering
+        // loses energy as roughness grows, down to 2 (1 - ln 2) / (1 + cos) at roughness 1
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let eyev = Vec3::new(0.3, 0.0, 1.0).normalize();
+        let mut rng = Rng::new(3, 0);
+        for (roughness, lowest) in [(0.2, 0.95), (0.5, 0.85), (1.0, 0.3)] {
+            let mut albedo = 0.0;
+            let count = 50_000;
+            for _ in 0..count {
+                let (u, v) = rng.next_2d();
+                let l = sample_microfacet(1.0, roughness, n, eyev, rng.next_f64(), u, v);
+                let pdf = microfacet_pdf(1.0, roughness, n, eyev, l);
+                if pdf > 0.0 {
+                    let f = microfacet_brdf(Color::white(), 1.0, roughness, n, eyev, l);
+                    albedo += f.r * (n ^ l) / pdf;
+                }
+            }
+            let albedo = albedo / count as f64;
+            assert!(albedo < 1.01 && albedo > lowest);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use super::{light::DirectionalLight, spectrum::xyz_to_linear_srgb};
+use crate::primitives::{color::Color, tuple::Tuple, vec3::Vec3};
+
+// analytic daylight sky after Preetham, Shirley and Smits (1999), "A Practical Analytic Model
+// for Daylight". Angles are in radians, azimuth is measured from -z towards +x and turbidity
+// is meaningful roughly between 2 (very clear) and 10 (hazy). The sun disk itself is not part
+// of the sky, pair it with `sun()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sky {
+    sun_elevation: f64,
+    sun_azimuth: f64,
+    turbidity: f64,
+    // converts the model's luminance, in kcd/m^2, to scene radiance
+    scale: f64,
+}
+
+// Perez distribution coefficients A..E
+type Perez = [f64; 5];
+
+impl Sky {
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64) -> Self {
+        Sky {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            scale: 0.05,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn sun_elevation(&self) -> f64 {
+        self.sun_elevation
+    }
+
+    pub fn sun_azimuth(&self) -> f64 {
+        self.sun_azimuth
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    // unit vector pointing at the sun
+    pub fn sun_direction(&self) -> Vec3 {
+        let (el, az) = (self.sun_elevation, self.sun_azimuth);
+        Vec3::new(el.cos() * az.sin(), el.sin(), -el.cos() * az.cos())
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        // directions below the horizon see the horizon color
+        let d = Vec3::new(direction.x(), direction.y().max(0.0), direction.z()).normalize();
+        let t = self.turbidity;
+        // the model is only defined for a sun above the horizon
+        let theta_s = FRAC_PI_2 - self.sun_elevation.clamp(0.0, FRAC_PI_2);
+        let cos_theta = d.y().max(0.001);
+        let cos_gamma = (d ^ self.sun_direction()).clamp(-1.0, 1.0);
+
+        let perez_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let perez_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let perez_yc = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        let (zenith_y, zenith_x, zenith_yc) = zenith(t, theta_s);
+        let relative = |p: &Perez| perez(p, cos_theta, cos_gamma) / perez(p, 1.0, theta_s.cos());
+
+        let luminance = zenith_y * relative(&perez_y);
+        let x = zenith_x * relative(&perez_x);
+        let y = zenith_yc * relative(&perez_yc);
+
+        xyy_to_linear_srgb(x, y, luminance) * self.scale
+    }
+
+    // directional light shining from the sun, reddened by the air mass it travels through
+    pub fn sun(&self, intensity: f64) -> DirectionalLight {
+        let color = if self.sun_elevation <= 0.0 {
+            Color::black()
+        } else {
+            sun_transmittance(FRAC_PI_2 - self.sun_elevation, self.turbidity) * intensity
+        };
+        DirectionalLight::new(-self.sun_direction(), color)
+    }
+}
+
+// zenith luminance (kcd/m^2) and chromaticity for turbidity `t` and sun zenith angle `theta_s`
+fn zenith(t: f64, theta_s: f64) -> (f64, f64, f64) {
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+    let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+    let t2 = t * t;
+    let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+        + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+        + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+    let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+        + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+        + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+    (luminance, x, y)
+}
+
+fn perez(p: &Perez, cos_theta: f64, cos_gamma: f64) -> f64 {
+    let gamma = cos_gamma.acos();
+    (1.0 + p[0] * f64::exp(p[1] / cos_theta))
+        * (1.0 + p[2] * f64::exp(p[3] * gamma) + p[4] * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_linear_srgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::black();
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    let c = xyz_to_linear_srgb(cx, luminance, cz);
+    Color::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0))
+}
+
+// Rayleigh and aerosol extinction from the paper's appendix, evaluated at one wavelength
+// per channel; ozone and water vapour absorption are left out
+fn sun_transmittance(theta_s: f64, turbidity: f64) -> Color {
+    let degrees = theta_s.to_degrees();
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * f64::powf(93.885 - degrees, -1.253));
+    let beta = 0.04608365822050 * turbidity - 0.04586025928522;
+
+    // wavelengths in micrometers
+    let channel = |lambda: f64| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        f64::exp(-air_mass * (rayleigh + aerosol))
+    };
+    Color::new(channel(0.65), channel(0.57), channel(0.475))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::float::ApproxEq;
+    use std::f64::consts::FRAC_PI_4;
+
+    #[test]
+    fn sun_direction_follows_elevation_and_azimuth() {
+        assert_eq!(
+            Sky::new(FRAC_PI_2, 0.0, 3.0).sun_direction(),
+            Vec3::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            Sky::new(0.0, 0.0, 3.0).sun_direction(),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+        assert_eq!(
+            Sky::new(0.0, FRAC_PI_2, 3.0).sun_direction(),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn clear_sky_is_blue_overhead() {
+        let sky = Sky::new(FRAC_PI_4, 0.0, 2.5);
+        let zenith = sky.radiance(Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.b > zenith.r);
+    }
+
+    #[test]
+    fn sky_is_brighter_towards_the_sun() {
+        let sky = Sky::new(0.3, 1.0, 3.0);
+        let sun = sky.sun_direction();
+        let away = Vec3::new(-sun.x(), sun.y(), -sun.z());
+        let near = sky.radiance(Vec3::new(sun.x(), sun.y() + 0.1, sun.z()));
+        assert!(near.luminance() > sky.radiance(away).luminance());
+    }
+
+    #[test]
+    fn haze_whitens_the_sky() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let clear = Sky::new(FRAC_PI_4, 0.0, 2.0).radiance(up);
+        let hazy = Sky::new(FRAC_PI_4, 0.0, 8.0).radiance(up);
+        assert!(hazy.r / hazy.b > clear.r / clear.b);
+    }
+
+    #[test]
+    fn below_the_horizon_sees_the_horizon() {
+        let sky = Sky::new(0.5, 0.0, 3.0);
+        assert_eq!(
+            sky.radiance(Vec3::new(1.0, -0.5, 0.0)),
+            sky.radiance(Vec3::new(1.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn scale_multiplies_radiance() {
+        let d = Vec3::new(0.2, 0.5, -1.0);
+        let sky = Sky::new(0.6, 0.3, 4.0);
+        let doubled = sky.with_scale(sky.scale() * 2.0);
+        assert!(doubled.radiance(d).g.approx_eq(sky.radiance(d).g * 2.0));
+    }
+
+    #[test]
+    fn sun_light_shines_away_from_the_sun() {
+        let sky = Sky::new(FRAC_PI_4, 0.5, 3.0);
+        assert_eq!(sky.sun(1.0).direction(), -sky.sun_direction());
+    }
+
+    #[test]
+    fn low_sun_is_dimmer_and_redder() {
+        let noon = Sky::new(1.3, 0.0, 3.0).sun(1.0).intensity();
+        let sunset = Sky::new(0.05, 0.0, 3.0).sun(1.0).intensity();
+        assert!(sunset.luminance() < noon.luminance());
+        assert!(sunset.r / sunset.b > noon.r / noon.b);
+        assert!(noon.r <= 1.0 && noon.b < noon.r);
+    }
+
+    #[test]
+    fn sun_below_horizon_gives_no_light() {
+        let sun = Sky::new(-0.1, 0.0, 3.0).sun(1.0);
+        assert_eq!(sun.intensity(), Color::black());
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::OnceLock;
+
+use crate::primitives::color::Color;
+
+// visible range sampled by spectral renders, in nanometers
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 730.0;
+
+// how the refractive index of a material changes with wavelength
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Dispersion {
+    // `Material::refractive_index` at every wavelength
+    #[default]
+    None,
+    // n = a + b / lambda^2, lambda in micrometers
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    // n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Dispersion {
+    // Schott N-BK7 crown glass
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    // diamond, after Peter (1923)
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    // refractive index at `wavelength` nanometers, None when it doesn't depend on it
+    pub fn index(&self, wavelength: f64) -> Option<f64> {
+        let l = wavelength / 1000.0;
+        let l2 = l * l;
+        match self {
+            Dispersion::None => None,
+            Dispersion::Cauchy { a, b } => Some(a + b / l2),
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                Some(f64::sqrt(1.0 + sum))
+            }
+        }
+    }
+}
+
+// CIE 1931 2° color matching functions at `wavelength` nanometers, using the multi-lobe fit
+// from Wyman, Sloan and Shirley (2013), "Simple Analytic Approximations to the CIE XYZ Color
+// Matching Functions"
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let g = |mu: f64, below: f64, above: f64| {
+        let sigma = if wavelength < mu { below } else { above };
+        let t = (wavelength - mu) / sigma;
+        f64::exp(-0.5 * t * t)
+    };
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+// CIE XYZ to linear sRGB (D65), unclamped so that out of gamut samples still average out
+pub fn xyz_to_linear_srgb(x: f64, y: f64, z: f64) -> Color {
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+// weight of a sample carrying `wavelength` nanometers, chosen uniformly over the visible range.
+// Averaged over the range it is white, so a scene without dispersion renders as in RGB
+pub fn wavelength_to_color(wavelength: f64) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    xyz_to_linear_srgb(x, y, z) * white_balance()
+}
+
+// per channel inverse of the mean of the unbalanced `wavelength_to_color`
+fn white_balance() -> Color {
+    static BALANCE: OnceLock<Color> = OnceLock::new();
+    *BALANCE.get_or_init(|| {
+        let steps = 1000;
+        let mut mean = Color::black();
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) / steps as f64 * (LAMBDA_MAX - LAMBDA_MIN);
+            let (x, y, z) = cie_xyz(lambda);
+            mean += xyz_to_linear_srgb(x, y, z) * (1.0 / steps as f64);
+        }
+        Color::new(1.0 / mean.r, 1.0 / mean.g, 1.0 / mean.b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::float::ApproxEq;
+
+    #[test]
+    fn luminance_peaks_in_the_green() {
+        let (_, y, _) = cie_xyz(555.0);
+        assert!((y - 1.0).abs() < 0.02);
+        assert!(cie_xyz(450.0).1 < 0.1 && cie_xyz(650.0).1 < 0.15);
+    }
+
+    #[test]
+    fn wavelengths_have_their_hue() {
+        let blue = wavelength_to_color(450.0);
+        assert!(blue.b > blue.g && blue.b > blue.r);
+        let green = wavelength_to_color(530.0);
+        assert!(green.g > green.r && green.g > green.b);
+        let red = wavelength_to_color(640.0);
+        assert!(red.r > red.g && red.r > red.b);
+    }
+
+    #[test]
+    fn the_visible_range_averages_to_white() {
+        let steps = 350;
+        let mut mean = Color::black();
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+            mean += wavelength_to_color(lambda) * (1.0 / steps as f64);
+        }
+        assert!(mean.r.approx_eq_low_precision(1.0));
+        assert!(mean.g.approx_eq_low_precision(1.0));
+        assert!(mean.b.approx_eq_low_precision(1.0));
+    }
+
+    #[test]
+    fn sellmeier_matches_the_catalogue() {
+        // N-BK7 at the helium d line
+        let n = Dispersion::bk7().index(587.56).unwrap();
+        assert!((n - 1.5168).abs() < 1e-4);
+        let diamond = Dispersion::diamond().index(589.3).unwrap();
+        assert!((diamond - 2.417).abs() < 0.005);
+    }
+
+    #[test]
+    fn shorter_wavelengths_bend_more() {
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+        for d in [cauchy, Dispersion::bk7(), Dispersion::diamond()] {
+            assert!(d.index(450.0).unwrap() > d.index(650.0).unwrap());
+        }
+        assert_eq!(Dispersion::None.index(500.0), None);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::primitives::{point::Point, tuple::Tuple, vec3::Vec3};
 
 use super::{
-    object::{Intersection, IntersectionResult, Object},
+    object::{Intersection, IntersectionResult, Object, SurfaceSample},
     ray::Ray,
+    sampling::uniform_sphere,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -46,6 +47,30 @@ impl Sphere {
         return obj_p - self.center();
     }
 
+    // uniformly distributed point on the surface for (u, v) in [0, 1)^2
+    pub fn sample_surface(&self, u: f64, v: f64) -> SurfaceSample {
+        let normal = uniform_sphere(u, v);
+
+        SurfaceSample {
+            point: self.center + normal * self.radius,
+            normal,
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    // spherical (u, v) texture coordinates in [0, 1], v = 1 at the north pole
+    pub fn uv_at(&self, obj_p: Point) -> (f64, f64) {
+        let d = obj_p - self.center();
+        let theta = f64::atan2(d.x(), d.z());
+        let phi = f64::acos((d.y() / d.magnitude()).clamp(-1.0, 1.0));
+        let u = 1.0 - (theta / (2.0 * std::f64::consts::PI) + 0.5);
+        (u, 1.0 - phi / std::f64::consts::PI)
+    }
+
+    pub fn area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.radius * self.radius
+    }
+
     pub fn new(center: Point, radius: f64) -> Self {
         Sphere {
             center,
@@ -73,7 +98,7 @@ mod tests {
 
     use crate::{
         core::{light::Material, transforms::Transformations},
-        primitives::{color::Color, matrix4f::Matrix4f, vec3::Vec3},
+        primitives::{color::Color, float::ApproxEq, matrix4f::Matrix4f, vec3::Vec3},
     };
 
     use super::*;
@@ -224,4 +249,32 @@ mod tests {
         s.set_material(m);
         assert_eq!(s.material(), m);
     }
+
+    #[test]
+    fn surface_samples_lie_on_the_sphere() {
+        let s = Sphere::new(Point::new(1.0, 2.0, 3.0), 2.0);
+        for (u, v) in [(0.0, 0.0), (0.3, 0.7), (0.5, 0.5), (0.99, 0.2)] {
+            let sample = s.sample_surface(u, v);
+            assert!((sample.point - s.center()).magnitude().approx_eq(2.0));
+            assert_eq!(sample.normal, (sample.point - s.center()).normalize());
+            assert!(sample.pdf.approx_eq(1.0 / (16.0 * f64::consts::PI)));
+        }
+    }
+
+    #[test]
+    fn spherical_uv_mapping() {
+        let s = Sphere::default();
+        let cases = [
+            (Point::new(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (Point::new(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (Point::new(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (Point::new(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (Point::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Point::new(0.0, -1.0, 0.0), (0.5, 0.0)),
+        ];
+        for (p, (u, v)) in cases {
+            let (su, sv) = s.uv_at(p);
+            assert!(su.approx_eq(u) && sv.approx_eq(v));
+        }
+    }
 }
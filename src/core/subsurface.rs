@@ -0,0 +1,214 @@
+use super::{
+    object::Object,
+    random::Rng,
+    ray::Ray,
+    sampling::{cosine_hemisphere, uniform_sphere},
+};
+use crate::primitives::{color::Color, float::SURFACE_EPSILON, point::Point, vec3::Vec3};
+
+// light transport beneath the surface of translucent materials like skin, wax or marble,
+// simulated as a random walk through a homogeneous medium filling the object. `radius` is the
+// average distance light travels under the surface per channel, larger radii look softer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Subsurface {
+    radius: Color,
+    // walks averaged per shading point by `World`, the path tracer takes one per path
+    samples: usize,
+    max_bounces: usize,
+}
+
+// where a walk comes back out of the object, `normal` points out of it and `weight` is the
+// fraction of each channel that made it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Exit {
+    pub point: Point,
+    pub normal: Vec3,
+    pub weight: Color,
+}
+
+impl Subsurface {
+    pub fn new(radius: Color) -> Self {
+        Subsurface {
+            radius,
+            samples: 16,
+            max_bounces: 256,
+        }
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn with_max_bounces(mut self, max_bounces: usize) -> Self {
+        self.max_bounces = max_bounces;
+        self
+    }
+
+    pub fn radius(&self) -> Color {
+        self.radius
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn max_bounces(&self) -> usize {
+        self.max_bounces
+    }
+
+    // follows light entering `object` at `entry`, where `normalv` points out of the surface,
+    // until it leaves again. `albedo` is the color the object should have once all the light
+    // that comes back out is added up. None when the light is absorbed or lost
+    pub fn walk(
+        &self,
+        object: &Object,
+        albedo: Color,
+        entry: Point,
+        normalv: Vec3,
+        time: f64,
+        rng: &mut Rng,
+    ) -> Option<Exit> {
+        let extinction = channels(self.radius, |r| 1.0 / r.max(1e-6));
+        let scattering = channels(albedo, single_scattering_albedo) * extinction;
+        let mut throughput = Color::white();
+        let mut point = entry + normalv * -SURFACE_EPSILON;
+        let (u, v) = rng.next_2d();
+        let mut direction = cosine_hemisphere(-normalv, u, v);
+
+        for _ in 0..self.max_bounces {
+            let ray = Ray::new(point, direction).with_time(time);
+            let result = object.intersect(&ray);
+            let boundary = result
+                .i
+                .iter()
+                .map(|i| i.t)
+                .filter(|&t| t > SURFACE_EPSILON)
+                .fold(f64::INFINITY, f64::min);
+            if !result.ok || boundary.is_infinite() {
+                return None;
+            }
+
+            // distances are sampled from one channel at a time and weighed against picking
+            // them with any of the three
+            let sigma =
+                [extinction.r, extinction.g, extinction.b][(rng.next_f64() * 3.0) as usize % 3];
+            let t = -f64::ln(1.0 - rng.next_f64()) / sigma;
+
+            if t >= boundary {
+                let transmittance = channels(extinction, |s| f64::exp(-s * boundary));
+                throughput *= transmittance * (1.0 / average(transmittance));
+                let point = ray.position(boundary);
+                return Some(Exit {
+                    point,
+                    normal: object.normal_at_time(point, time),
+                    weight: throughput,
+                });
+            }
+
+            let transmittance = channels(extinction, |s| f64::exp(-s * t));
+            let pdf = average(extinction * transmittance);
+            throughput *= scattering * transmittance * (1.0 / pdf);
+
+            // nothing left to carry
+            let survival = throughput.r.max(throughput.g).max(throughput.b).min(1.0);
+            if survival <= 0.0 || rng.next_f64() >= survival {
+                return None;
+            }
+            throughput *= 1.0 / survival;
+
+            point = ray.position(t);
+            let (u, v) = rng.next_2d();
+            direction = uniform_sphere(u, v);
+        }
+
+        None
+    }
+}
+
+fn channels(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(c.r), f(c.g), f(c.b))
+}
+
+fn average(c: Color) -> f64 {
+    (c.r + c.g + c.b) / 3.0
+}
+
+// albedo of a single scattering event that makes a thick slab reflect `albedo` in total,
+// fitted by van de Hulst and used the same way in Cycles' random walk
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0.0, 0.999);
+    let s = 4.09712 + 4.20863 * a - f64::sqrt(9.59217 + 41.6808 * a + 17.7126 * a * a);
+    1.0 - s * s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tuple::Tuple;
+
+    fn walk_many(sss: Subsurface, albedo: Color, walks: u64) -> (Color, Vec<Exit>) {
+        let object = Object::sphere();
+        let entry = Point::new(0.0, 1.0, 0.0);
+        let normalv = Vec3::new(0.0, 1.0, 0.0);
+        let mut exits = vec![];
+        let mut total = Color::black();
+        for i in 0..walks {
+            let mut rng = Rng::new(3, i);
+            if let Some(exit) = sss.walk(&object, albedo, entry, normalv, 0.0, &mut rng) {
+                total += exit.weight * (1.0 / walks as f64);
+                exits.push(exit);
+            }
+        }
+        (total, exits)
+    }
+
+    #[test]
+    fn single_scattering_albedo_matches_the_extremes() {
+        assert!(single_scattering_albedo(0.0).abs() < 1e-3);
+        assert!(single_scattering_albedo(0.999) > 0.999);
+        assert!(single_scattering_albedo(0.5) > 0.5);
+    }
+
+    #[test]
+    fn walks_leave_through_the_surface() {
+        let sss = Subsurface::new(Color::new(0.2, 0.2, 0.2));
+        let (_, exits) = walk_many(sss, Color::white(), 64);
+        assert!(!exits.is_empty());
+        for exit in exits {
+            let p = exit.point;
+            let distance = f64::sqrt(p.x() * p.x() + p.y() * p.y() + p.z() * p.z());
+            assert!((distance - 1.0).abs() < 1e-6);
+            assert!((exit.normal ^ Vec3::new(p.x(), p.y(), p.z())) > 0.0);
+        }
+    }
+
+    #[test]
+    fn white_objects_give_back_most_of_the_light() {
+        let sss = Subsurface::new(Color::new(0.1, 0.1, 0.1));
+        let (total, _) = walk_many(sss, Color::white(), 512);
+        assert!(total.g > 0.8 && total.g < 1.1, "{total:?}");
+    }
+
+    #[test]
+    fn albedo_tints_the_light_that_comes_back() {
+        let sss = Subsurface::new(Color::new(0.1, 0.1, 0.1));
+        let (total, _) = walk_many(sss, Color::new(0.9, 0.5, 0.1), 512);
+        assert!(total.r > total.g && total.g > total.b);
+    }
+
+    #[test]
+    fn larger_radius_spreads_light_further() {
+        let spread = |radius: f64| {
+            let sss = Subsurface::new(Color::new(radius, radius, radius));
+            let (_, exits) = walk_many(sss, Color::white(), 256);
+            let entry = Point::new(0.0, 1.0, 0.0);
+            exits
+                .iter()
+                .map(|e| (e.point - entry).magnitude())
+                .sum::<f64>()
+                / exits.len() as f64
+        };
+        assert!(spread(0.5) > spread(0.05));
+    }
+}
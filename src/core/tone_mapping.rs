@@ -0,0 +1,134 @@
+use crate::primitives::color::Color;
+
+// operator used to compress scene-referred radiance into the displayable [0, 1] range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    // `white` is the smallest radiance that maps to pure white
+    ExtendedReinhard { white: f64 },
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn apply(&self, c: f64) -> f64 {
+        let c = c.max(0.0);
+        let mapped = match self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => c / (1.0 + c),
+            ToneMap::ExtendedReinhard { white } => c * (1.0 + c / (white * white)) / (1.0 + c),
+            // Narkowicz's curve fit of the ACES reference rendering transform
+            ToneMap::AcesFilmic => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+// exposure -> tone map -> sRGB encoding, applied to every pixel written by a canvas exporter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputTransform {
+    pub tone_map: ToneMap,
+    // exposure adjustment in stops, each stop doubles the incoming radiance
+    pub exposure: f64,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        OutputTransform {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+        }
+    }
+}
+
+impl OutputTransform {
+    pub fn new(tone_map: ToneMap, exposure: f64) -> Self {
+        OutputTransform { tone_map, exposure }
+    }
+
+    pub fn apply(&self, color: &Color) -> (u8, u8, u8) {
+        let scale = f64::powf(2.0, self.exposure);
+        let encode = |c: f64| {
+            let display = srgb_oetf(self.tone_map.apply(c * scale));
+            (display * 255.0).round() as u8
+        };
+        (encode(color.r), encode(color.g), encode(color.b))
+    }
+}
+
+// linear -> sRGB transfer function (IEC 61966-2-1)
+pub fn srgb_oetf(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::float::ApproxEq;
+
+    #[test]
+    fn srgb_oetf_endpoints_and_linear_segment() {
+        assert!(srgb_oetf(0.0).approx_eq(0.0));
+        assert!(srgb_oetf(1.0).approx_eq(1.0));
+        assert!(srgb_oetf(0.002).approx_eq(0.002 * 12.92));
+    }
+
+    #[test]
+    fn srgb_oetf_brightens_midtones() {
+        assert!(srgb_oetf(0.18).approx_eq_low_precision(0.46135));
+    }
+
+    #[test]
+    fn clamp_cuts_values_outside_display_range() {
+        assert_eq!(ToneMap::Clamp.apply(-0.5), 0.0);
+        assert_eq!(ToneMap::Clamp.apply(0.5), 0.5);
+        assert_eq!(ToneMap::Clamp.apply(3.0), 1.0);
+    }
+
+    #[test]
+    fn reinhard_compresses_highlights() {
+        assert!(ToneMap::Reinhard.apply(1.0).approx_eq(0.5));
+        assert!(ToneMap::Reinhard.apply(1000.0) < 1.0);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let t = ToneMap::ExtendedReinhard { white: 4.0 };
+        assert!(t.apply(4.0).approx_eq(1.0));
+        assert!(t.apply(1.0) > ToneMap::Reinhard.apply(1.0));
+    }
+
+    #[test]
+    fn aces_filmic_is_monotonic() {
+        let t = ToneMap::AcesFilmic;
+        assert!(t.apply(0.0).approx_eq(0.0));
+        assert!(t.apply(0.1) < t.apply(0.5));
+        assert!(t.apply(0.5) < t.apply(2.0));
+        assert_eq!(t.apply(100.0), 1.0);
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let transform = OutputTransform::new(ToneMap::Clamp, 1.0);
+        let unexposed = OutputTransform::default();
+        let c = Color::new(0.25, 0.25, 0.25);
+
+        assert_eq!(
+            transform.apply(&c),
+            unexposed.apply(&Color::new(0.5, 0.5, 0.5))
+        );
+    }
+
+    #[test]
+    fn default_transform_encodes_to_srgb() {
+        let transform = OutputTransform::default();
+
+        assert_eq!(transform.apply(&Color::black()), (0, 0, 0));
+        assert_eq!(transform.apply(&Color::white()), (255, 255, 255));
+        assert_eq!(transform.apply(&Color::new(0.5, 0.5, 0.5)), (188, 188, 188));
+    }
+}
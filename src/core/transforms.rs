@@ -1,5 +1,7 @@
 use crate::primitives::{
+    float::EPSILON,
     matrix4f::{Matrix4f, MATRIX_SIZE},
+    quaternion::Quaternion,
     tuple::Tuple,
     vec3::Vec3,
 };
@@ -157,6 +159,77 @@ impl Transformations {
     }
 }
 
+// a transformation split into translation * rotation * scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Decomposition {
+    // None when `m` squashes space flat along some axis, or shears it so that no rotation
+    // and per axis scale add up to it
+    pub fn new(m: &Matrix4f) -> Option<Self> {
+        let translation = Vec3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+        let column = |c: usize| Vec3::new(m[(0, c)], m[(1, c)], m[(2, c)]).magnitude();
+        let mut scale = Vec3::new(column(0), column(1), column(2));
+        // a mirrored basis can't be expressed as a rotation, fold the flip into the scale
+        if m.submatrix(3, 3).determinant() < 0.0 {
+            scale = Vec3::new(-scale.x(), scale.y(), scale.z());
+        }
+        if [scale.x(), scale.y(), scale.z()]
+            .iter()
+            .any(|s| s.abs() < EPSILON)
+        {
+            return None;
+        }
+
+        let mut rotation = Matrix4f::identity();
+        for row in 0..3 {
+            rotation[(row, 0)] = m[(row, 0)] / scale.x();
+            rotation[(row, 1)] = m[(row, 1)] / scale.y();
+            rotation[(row, 2)] = m[(row, 2)] / scale.z();
+        }
+        let axis = |c: usize| Vec3::new(rotation[(0, c)], rotation[(1, c)], rotation[(2, c)]);
+        if [(0, 1), (0, 2), (1, 2)]
+            .iter()
+            .any(|&(a, b)| (axis(a) ^ axis(b)).abs() > EPSILON)
+        {
+            return None;
+        }
+
+        Some(Decomposition {
+            translation,
+            rotation: Quaternion::from_rotation_matrix(&rotation),
+            scale,
+        })
+    }
+
+    pub fn interpolate(&self, other: &Decomposition, t: f64) -> Decomposition {
+        Decomposition {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    pub fn compose(&self) -> Matrix4f {
+        let (t, s) = (self.translation, self.scale);
+        Transformations::translate(t.x(), t.y(), t.z())
+            * self.rotation.to_matrix()
+            * Transformations::scale(s.x(), s.y(), s.z())
+    }
+
+    // inverse of `compose`, undone piece by piece instead of inverting the matrix
+    pub fn compose_inverse(&self) -> Matrix4f {
+        let (t, s) = (self.translation, self.scale);
+        Transformations::scale(1.0 / s.x(), 1.0 / s.y(), 1.0 / s.z())
+            * self.rotation.to_matrix().transpose()
+            * Transformations::translate(-t.x(), -t.y(), -t.z())
+    }
+}
+
 pub struct TransformChainer<T> {
     pub m: Matrix4f,
     pub x: T,
@@ -285,6 +358,65 @@ mod tests {
         assert_eq!(p, Point::new(15.0, 0.0, 7.0));
     }
 
+    #[test]
+    fn decomposition_recomposes_to_the_same_matrix() {
+        let m = Transformations::translate(1.0, -2.0, 3.0)
+            * Transformations::rotate_y(f64::consts::FRAC_PI_3)
+            * Transformations::scale(2.0, 0.5, 1.5);
+        let d = Decomposition::new(&m).unwrap();
+
+        assert_eq!(d.translation, Vec3::new(1.0, -2.0, 3.0));
+        assert_eq!(d.scale, Vec3::new(2.0, 0.5, 1.5));
+        assert_eq!(d.compose(), m);
+
+        let mirrored = Transformations::scale(-1.0, 1.0, 1.0);
+        assert_eq!(Decomposition::new(&mirrored).unwrap().compose(), mirrored);
+    }
+
+    #[test]
+    fn decomposition_inverse_undoes_the_transformation() {
+        let m = Transformations::translate(1.0, -2.0, 3.0)
+            * Transformations::rotate_x(0.7)
+            * Transformations::scale(2.0, -0.5, 1.5);
+        let d = Decomposition::new(&m).unwrap();
+        assert_eq!(d.compose_inverse(), m.invert().unwrap());
+    }
+
+    #[test]
+    fn flat_transformations_have_no_decomposition() {
+        assert_eq!(
+            Decomposition::new(&Transformations::scale(1.0, 0.0, 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn sheared_transformations_have_no_decomposition() {
+        let m = Transformations::scale(1.0, 3.0, 1.0) * Transformations::rotate_z(0.6);
+        assert_eq!(Decomposition::new(&m), None);
+        assert_eq!(
+            Decomposition::new(&Transformations::shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn interpolated_decomposition_moves_along_the_motion() {
+        let start = Decomposition::new(&Matrix4f::identity()).unwrap();
+        let end = Decomposition::new(
+            &(Transformations::translate(4.0, 0.0, 0.0)
+                * Transformations::rotate_z(f64::consts::FRAC_PI_2)
+                * Transformations::scale(3.0, 3.0, 3.0)),
+        )
+        .unwrap();
+        let half = start.interpolate(&end, 0.5).compose();
+        let expected = Transformations::translate(2.0, 0.0, 0.0)
+            * Transformations::rotate_z(f64::consts::FRAC_PI_4)
+            * Transformations::scale(2.0, 2.0, 2.0);
+
+        assert_eq!(half, expected);
+    }
+
     #[test]
     fn view_transformation_for_default_orientation() {
         let t = Transformations::view_transform(
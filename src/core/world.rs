@@ -1,14 +1,33 @@
+use std::f64::consts::PI;
+
 use super::{
-    light::{lighting, Material, PointLight},
+    background::Background,
+    environment::EnvironmentLight,
+    light::{lighting, Light, Material},
+    medium::{beer_lambert, Medium, Volume},
     object::{IntersectionState, Intersections, Object},
+    photon_map::PhotonMap,
+    random::Rng,
     ray::Ray,
+    sampling::{hash, random_1d, random_2d},
+    subsurface::Subsurface,
     transforms::Transformations,
 };
-use crate::primitives::{color::Color, point::Point, tuple::Tuple};
+use crate::primitives::{
+    color::Color, float::SURFACE_EPSILON, point::Point, tuple::Tuple, vec3::Vec3,
+};
+
+// how many reflected and refracted bounces a primary ray may spawn
+pub const MAX_RECURSION_DEPTH: usize = 5;
 
 pub struct World {
     objects: Vec<Object>,
-    light_sources: Vec<PointLight>,
+    light_sources: Vec<Light>,
+    background: Background,
+    environment_light: Option<EnvironmentLight>,
+    fog: Option<Medium>,
+    volumes: Vec<Volume>,
+    caustics: Option<PhotonMap>,
 }
 
 impl Default for World {
@@ -23,10 +42,12 @@ impl Default for World {
         s2.set_transformation(Transformations::scale(0.5, 0.5, 0.5));
         World {
             objects: vec![s1, s2],
-            light_sources: vec![PointLight::new(
-                Point::new(-10.0, 10.0, -10.0),
-                Color::white(),
-            )],
+            light_sources: vec![Light::point(Point::new(-10.0, 10.0, -10.0), Color::white())],
+            background: Background::default(),
+            environment_light: None,
+            fog: None,
+            volumes: vec![],
+            caustics: None,
         }
     }
 }
@@ -36,6 +57,11 @@ impl World {
         return World {
             objects: vec![],
             light_sources: vec![],
+            background: Background::default(),
+            environment_light: None,
+            fog: None,
+            volumes: vec![],
+            caustics: None,
         };
     }
 
@@ -47,18 +73,86 @@ impl World {
         self.objects.push(obj);
     }
 
-    pub fn push_light_source(&mut self, obj: PointLight) {
-        self.light_sources.push(obj);
+    pub fn push_light_source(&mut self, light: impl Into<Light>) {
+        self.light_sources.push(light.into());
     }
 
-    pub fn light_sources(&self) -> &Vec<PointLight> {
+    // objects whose material emits light, sampled as lights by integrators that support it
+    pub fn emitters(&self) -> Vec<&Object> {
+        self.objects.iter().filter(|o| o.is_emissive()).collect()
+    }
+
+    pub fn light_sources(&self) -> &Vec<Light> {
         return &self.light_sources;
     }
 
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    // image-based lighting, independent from the background seen by rays that miss
+    pub fn set_environment_light(&mut self, environment: EnvironmentLight) {
+        self.environment_light = Some(environment);
+    }
+
+    pub fn environment_light(&self) -> Option<&EnvironmentLight> {
+        self.environment_light.as_ref()
+    }
+
+    // homogeneous medium filling the whole scene
+    pub fn set_fog(&mut self, fog: Medium) {
+        self.fog = Some(fog);
+    }
+
+    pub fn fog(&self) -> Option<&Medium> {
+        self.fog.as_ref()
+    }
+
+    pub fn push_volume(&mut self, volume: Volume) {
+        self.volumes.push(volume);
+    }
+
+    pub fn volumes(&self) -> &Vec<Volume> {
+        &self.volumes
+    }
+
+    // caustic photon map added to the shading of diffuse surfaces, see `PhotonMap::caustics`
+    pub fn set_caustics(&mut self, caustics: PhotonMap) {
+        self.caustics = Some(caustics);
+    }
+
+    pub fn caustics(&self) -> Option<&PhotonMap> {
+        self.caustics.as_ref()
+    }
+
+    // light focused onto the hit by reflective and transparent objects, black without a
+    // caustic photon map and on the specular surfaces that don't store photons
+    pub fn caustic_color(&self, state: &IntersectionState) -> Color {
+        let material = state.object.material();
+        match &self.caustics {
+            Some(caustics) if material.reflective == 0.0 && material.transparency == 0.0 => {
+                caustics.estimate(state.point, state.normalv, |lightv| {
+                    material.brdf(state.normalv, state.eyev, lightv)
+                })
+            }
+            _ => Color::black(),
+        }
+    }
+
     pub fn intersect_world(&self, ray: &Ray) -> Intersections<'_> {
+        self.intersect_world_counting(ray, &mut 0)
+    }
+
+    // like `intersect_world`, adding the number of ray-object tests performed to `tests`
+    pub fn intersect_world_counting(&self, ray: &Ray, tests: &mut usize) -> Intersections<'_> {
         let mut intersections = Intersections::new();
 
         for obj in &self.objects {
+            *tests += 1;
             let intersection = obj.intersect(ray);
 
             if intersection.ok {
@@ -74,40 +168,440 @@ impl World {
     }
 
     pub fn shade_hit(&self, state: IntersectionState) -> Color {
-        let mut color = Color::black();
+        self.shade_hit_with_depth(state, MAX_RECURSION_DEPTH)
+    }
+
+    pub fn shade_hit_with_depth(&self, state: IntersectionState, remaining: usize) -> Color {
+        self.shade(state, remaining, true)
+    }
+
+    // `primary` when `state` is where the camera ray landed, see `glossy_average`
+    fn shade(&self, state: IntersectionState, remaining: usize, primary: bool) -> Color {
+        let material = state.object.material();
+        let mut surface = material.emitted();
+        let shell = material.shell();
         for light in self.light_sources.iter() {
-            color += lighting(
-                state.object.material(),
+            let visibility = self.light_visibility(light, state.over_point, state.time, state.seed);
+            surface += lighting(
+                shell,
                 light,
                 state.point,
                 state.eyev,
                 state.normalv,
+                visibility,
+            );
+        }
+        surface += self.caustic_color(&state);
+        if let Some(subsurface) = material.subsurface_scattering() {
+            surface += self.subsurface_color(&state, &subsurface) * material.diffuse;
+        }
+
+        if let Some(environment) = &self.environment_light {
+            surface += environment.shade(
+                &material,
+                state.normalv,
+                state.eyev,
+                shading_seed(state.seed, state.over_point),
+                |direction| {
+                    let ray = Ray::new(state.over_point, direction).with_time(state.time);
+                    self.shadow_ray_visibility(&ray, f64::INFINITY)
+                },
             );
         }
-        return color;
+
+        let reflected = self.reflected_color(&state, remaining, primary);
+        let refracted = self.refracted_color(&state, remaining, primary);
+
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = state.schlick();
+            return surface + reflected * reflectance + refracted * (1.0 - reflectance);
+        }
+        surface + reflected + refracted
+    }
+
+    // direct light entering the surface where the walks come back out, averaged over the
+    // subsurface samples
+    fn subsurface_color(&self, state: &IntersectionState, subsurface: &Subsurface) -> Color {
+        let material = state.object.material();
+        // white and lambertian, the walk itself carries the color
+        let exit_material = Material {
+            ambient: 0.0,
+            diffuse: 1.0,
+            specular: 0.0,
+            ..Default::default()
+        };
+        let seed = shading_seed(state.seed, state.over_point) as u64;
+        let mut total = Color::black();
+        for i in 0..subsurface.samples() {
+            let mut rng = Rng::new(seed, i as u64);
+            let Some(exit) = subsurface.walk(
+                state.object,
+                material.color,
+                state.point,
+                state.normalv,
+                state.time,
+                &mut rng,
+            ) else {
+                continue;
+            };
+            let over_point = exit.point + exit.normal * SURFACE_EPSILON;
+            for light in self.light_sources.iter() {
+                let visibility = self.light_visibility(light, over_point, state.time, state.seed);
+                total += exit.weight
+                    * lighting(
+                        exit_material,
+                        light,
+                        exit.point,
+                        exit.normal,
+                        exit.normal,
+                        visibility,
+                    );
+            }
+        }
+        total * (1.0 / subsurface.samples() as f64)
+    }
+
+    pub fn reflected_color(
+        &self,
+        state: &IntersectionState,
+        remaining: usize,
+        primary: bool,
+    ) -> Color {
+        let material = state.object.material();
+        if material.reflective == 0.0 || remaining == 0 {
+            return Color::black();
+        }
+        let color = self.glossy_average(state, primary, |i, seed| {
+            self.trace(reflected_ray(state, i, seed), remaining - 1, false)
+        });
+        color * material.reflective
+    }
+
+    pub fn refracted_color(
+        &self,
+        state: &IntersectionState,
+        remaining: usize,
+        primary: bool,
+    ) -> Color {
+        let material = state.object.material();
+        if material.transparency == 0.0 || remaining == 0 {
+            return Color::black();
+        }
+
+        // no refracted ray under total internal reflection
+        let n_ratio = state.n1 / state.n2;
+        if Vec3::refract(state.eyev, state.normalv, n_ratio).is_none() {
+            return Color::black();
+        }
+
+        let color = self.glossy_average(state, primary, |i, seed| {
+            match refracted_ray(state, i, seed) {
+                Some(ray) => self.trace(ray, remaining - 1, false),
+                None => Color::black(),
+            }
+        });
+        color * material.transparency
+    }
+
+    // ray-object tests `shade_hit_with_depth` spends on the shadow rays towards the light
+    // sources and on the reflected and refracted rays below `state`. The media, subsurface
+    // walks and environment light are left out
+    pub fn intersection_tests(
+        &self,
+        state: &IntersectionState,
+        remaining: usize,
+        primary: bool,
+    ) -> usize {
+        let mut tests = 0;
+        let seed = shading_seed(state.seed, state.over_point);
+        for light in &self.light_sources {
+            for (direction, _) in light.shadow_rays(state.over_point, seed) {
+                let ray = Ray::new(state.over_point, direction).with_time(state.time);
+                self.intersect_world_counting(&ray, &mut tests);
+            }
+        }
+        if remaining == 0 {
+            return tests;
+        }
+
+        let material = state.object.material();
+        let mut follow = |ray: Ray| {
+            let xs = self.intersect_world_counting(&ray, &mut tests);
+            if let Some(hit) = xs.hit() {
+                let state = IntersectionState::prepare(*hit, ray, &xs);
+                tests += self.intersection_tests(&state, remaining - 1, false);
+            }
+        };
+        let samples = glossy_samples(&material, primary);
+        let seed = shading_seed(state.seed, state.over_point);
+        if material.reflective > 0.0 {
+            (0..samples).for_each(|i| follow(reflected_ray(state, i, seed)));
+        }
+        if material.transparency > 0.0 {
+            (0..samples)
+                .filter_map(|i| refracted_ray(state, i, seed))
+                .for_each(&mut follow);
+        }
+        tests
+    }
+
+    // mean of `sample` over the material's glossy samples. Only the first bounce, `primary`,
+    // takes more than one, so nested rough surfaces don't multiply the cost
+    fn glossy_average(
+        &self,
+        state: &IntersectionState,
+        primary: bool,
+        sample: impl Fn(usize, u32) -> Color,
+    ) -> Color {
+        let samples = glossy_samples(&state.object.material(), primary);
+        let seed = shading_seed(state.seed, state.over_point);
+        let mut total = Color::black();
+        for i in 0..samples {
+            total += sample(i, seed);
+        }
+        total * (1.0 / samples as f64)
+    }
+
+    // whether anything blocks `ray` before it has travelled `distance`
+    pub fn is_occluded(&self, ray: &Ray, distance: f64) -> bool {
+        match self.intersect_world(ray).hit() {
+            Some(hit) => hit.t < distance,
+            None => false,
+        }
+    }
+
+    // light left at the end of a shadow ray that travels `distance` along `ray`, black when
+    // something blocks it and dimmed by the fog and the volumes it crosses otherwise
+    pub fn shadow_ray_visibility(&self, ray: &Ray, distance: f64) -> Color {
+        if self.is_occluded(ray, distance) {
+            return Color::black();
+        }
+        self.shadow_transmittance(ray, distance)
+    }
+
+    // fraction of `light` reaching `point` per channel, between black (umbra) and white (fully
+    // lit and no media in the way). `seed` jitters the shadow rays of area lights, see
+    // `Ray::seed`
+    pub fn light_visibility(&self, light: &Light, point: Point, time: f64, seed: u32) -> Color {
+        let rays = light.shadow_rays(point, shading_seed(seed, point));
+        let total = rays
+            .iter()
+            .fold(Color::black(), |total, (direction, distance)| {
+                let ray = Ray::new(point, *direction).with_time(time);
+                total + self.shadow_ray_visibility(&ray, *distance)
+            });
+        total * (1.0 / rays.len() as f64)
     }
 
     pub fn color_at(&self, ray: Ray) -> Color {
+        self.color_at_with_depth(ray, MAX_RECURSION_DEPTH)
+    }
+
+    pub fn color_at_with_depth(&self, ray: Ray, remaining: usize) -> Color {
+        self.trace(ray, remaining, true)
+    }
+
+    // `primary` for camera rays, see `glossy_average`
+    fn trace(&self, ray: Ray, remaining: usize, primary: bool) -> Color {
         let intersections = self.intersect_world(&ray);
         let hit = intersections.hit();
 
         if hit.is_none() {
-            return Color::black();
+            let background = self.background.color(ray.direction());
+            let (transmittance, inscattered) = self.media_along(&ray, f64::INFINITY);
+            return background * transmittance + inscattered;
         }
 
         let hit = hit.unwrap();
-        let state = IntersectionState::new(*hit, ray);
-        let color = self.shade_hit(state);
+        let state = IntersectionState::prepare(*hit, ray, &intersections);
+        let transmittance = state.transmittance;
+        let color = self.shade(state, remaining, primary);
 
-        return color;
+        let (through_media, inscattered) = self.media_along(&ray, hit.t);
+        return color * transmittance * through_media + inscattered;
     }
+
+    // what the fog and the volumes do to light travelling back along `ray` from `t` = `end`,
+    // as the fraction that gets through and the light scattered into the ray on the way.
+    // In-scattering is ray marched with shadow rays towards every light source
+    pub fn media_along(&self, ray: &Ray, end: f64) -> (Color, Color) {
+        let mut transmittance = Color::white();
+        let mut inscattered = Color::black();
+        if self.fog.is_none() && self.volumes.is_empty() {
+            return (transmittance, inscattered);
+        }
+
+        let speed = ray.direction().magnitude();
+        // camera rays share their origin, the direction keeps the jitter apart between pixels
+        let d = ray.direction();
+        let seed = shading_seed(
+            ray.seed() ^ coordinates_hash(d.x(), d.y(), d.z()),
+            ray.origin(),
+        );
+        for (start, stop, media) in self.media_segments(ray, end, true) {
+            let extinction = media
+                .iter()
+                .fold(Color::black(), |sum, m| sum + m.extinction());
+            let length = (stop - start) * speed;
+            // an endless segment only matters until it has taken away nearly all light
+            let marched = length.min(opaque_distance(extinction));
+            if marched.is_infinite() {
+                continue;
+            }
+            let steps = media.iter().map(|m| m.steps()).max().unwrap_or(1);
+            let dt = marched / steps as f64;
+            for i in 0..steps {
+                let d = (i as f64 + random_1d(seed, i, 2)) * dt;
+                let point = ray.position(start + d / speed);
+                inscattered += transmittance
+                    * beer_lambert(extinction, d)
+                    * self.inscattering(point, ray, &media)
+                    * dt;
+            }
+            transmittance *= beer_lambert(extinction, length);
+        }
+
+        (transmittance, inscattered)
+    }
+
+    // pieces of `ray` between `t` = 0 and `end` that lie in some medium, with the media there
+    fn media_segments(&self, ray: &Ray, end: f64, with_fog: bool) -> Vec<(f64, f64, Vec<&Medium>)> {
+        let spans: Vec<(&Medium, Vec<(f64, f64)>)> = self
+            .volumes
+            .iter()
+            .map(|v| (v.medium(), v.spans(ray)))
+            .collect();
+
+        let mut cuts = vec![0.0, end];
+        for (_, s) in &spans {
+            for &(a, b) in s {
+                cuts.extend([a, b]);
+            }
+        }
+        cuts.retain(|t| (0.0..=end).contains(t));
+        cuts.sort_by(|a, b| a.total_cmp(b));
+        cuts.dedup();
+
+        cuts.windows(2)
+            .filter_map(|w| {
+                let (a, b) = (w[0], w[1]);
+                let mid = if b.is_finite() {
+                    (a + b) / 2.0
+                } else {
+                    a + 1.0
+                };
+                let mut media: Vec<&Medium> = match self.fog.as_ref() {
+                    Some(fog) if with_fog => vec![fog],
+                    _ => vec![],
+                };
+                for (m, s) in &spans {
+                    if s.iter().any(|&(s0, s1)| s0 <= mid && mid <= s1) {
+                        media.push(*m);
+                    }
+                }
+                (!media.is_empty()).then_some((a, b, media))
+            })
+            .collect()
+    }
+
+    // light from the light sources scattered at `point` towards the origin of `ray`, scaled by
+    // pi like the lights in `PathTracer` so that a medium and a white surface match
+    fn inscattering(&self, point: Point, ray: &Ray, media: &[&Medium]) -> Color {
+        let travel = ray.direction().normalize();
+        let mut total = Color::black();
+        for light in &self.light_sources {
+            let rays = light.shadow_rays(point, shading_seed(ray.seed(), point));
+            let share = PI / rays.len() as f64;
+            for (direction, distance) in rays {
+                let shadow = Ray::new(point, direction).with_time(ray.time());
+                let intensity = light.sample(point).intensity
+                    * light.attenuation().factor(distance)
+                    * self.shadow_ray_visibility(&shadow, distance);
+                let cos = -(direction ^ travel);
+                for m in media {
+                    total += intensity * m.scattering() * (m.phase(cos) * share);
+                }
+            }
+        }
+        total
+    }
+
+    // light left after the media along a shadow ray. The fog is taken to thin out past the
+    // scene, so light from infinitely far away, like the sun, only dims in the volumes
+    fn shadow_transmittance(&self, ray: &Ray, distance: f64) -> Color {
+        let speed = ray.direction().magnitude();
+        let with_fog = distance.is_finite();
+        self.media_segments(ray, distance, with_fog).iter().fold(
+            Color::white(),
+            |t, (start, stop, media)| {
+                let extinction = media
+                    .iter()
+                    .fold(Color::black(), |sum, m| sum + m.extinction());
+                t * beer_lambert(extinction, (stop - start) * speed)
+            },
+        )
+    }
+}
+
+// rays averaged by `glossy_average` for `material`
+fn glossy_samples(material: &Material, primary: bool) -> usize {
+    if material.roughness > 0.0 && primary {
+        material.glossy_samples.max(1)
+    } else {
+        1
+    }
+}
+
+// `i`th reflected ray of a glossy average seeded with `seed`
+fn reflected_ray(state: &IntersectionState, i: usize, seed: u32) -> Ray {
+    let (u, v) = random_2d(seed, i, 0);
+    state.spawn_ray(state.over_point, state.glossy_reflection(u, v))
+}
+
+// `i`th refracted ray of a glossy average seeded with `seed`, None under total internal
+// reflection
+fn refracted_ray(state: &IntersectionState, i: usize, seed: u32) -> Option<Ray> {
+    let (u, v) = random_2d(seed, i, 1);
+    let direction = state.glossy_refraction(u, v)?;
+    Some(state.spawn_ray(state.under_point, direction))
+}
+
+// distance after which less than a thousandth of the light is left in every channel that the
+// medium takes anything from
+fn opaque_distance(extinction: Color) -> f64 {
+    let weakest = [extinction.r, extinction.g, extinction.b]
+        .into_iter()
+        .filter(|&s| s > 0.0)
+        .fold(f64::INFINITY, f64::min);
+    f64::ln(1000.0) / weakest
+}
+
+// seed for the random choices made at `p` by the sample with `seed`, decorrelates them between
+// shading points
+fn shading_seed(seed: u32, p: Point) -> u32 {
+    hash(seed ^ coordinates_hash(p.x(), p.y(), p.z()))
+}
+
+fn coordinates_hash(x: f64, y: f64, z: f64) -> u32 {
+    let bits = |v: f64| {
+        let b = v.to_bits();
+        (b ^ (b >> 32)) as u32
+    };
+    hash(bits(x) ^ hash(bits(y) ^ hash(bits(z))))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        core::{light::Material, object::Intersection, transforms::Transformations},
-        primitives::vec3::Vec3,
+        core::{
+            canvas::Canvas,
+            light::{AreaLight, Material},
+            object::Intersection,
+            shading::ShadingModel,
+            spectrum::Dispersion,
+            transforms::Transformations,
+        },
+        primitives::{float::ApproxEq, vec3::Vec3},
     };
 
     use super::*;
@@ -122,7 +616,7 @@ mod tests {
 
     #[test]
     fn default_world() {
-        let light_source = PointLight::new(Point::new(-10.0, 10.0, -10.0), Color::white());
+        let light_source = Light::point(Point::new(-10.0, 10.0, -10.0), Color::white());
         let s1 = Object::sphere().with_material(Material {
             color: Color::new(0.8, 1.0, 0.6),
             diffuse: 0.7,
@@ -166,7 +660,7 @@ mod tests {
     #[test]
     fn shading_an_intersection_from_inside() {
         let mut w = World::default();
-        w.light_sources = vec![PointLight::new(Point::new(0.0, 0.25, 0.0), Color::white())];
+        w.light_sources = vec![Light::point(Point::new(0.0, 0.25, 0.0), Color::white())];
 
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
         let shape = w.objects[1];
@@ -214,4 +708,607 @@ mod tests {
 
         assert_eq!(c, inner.material().color);
     }
+
+    fn light_at(world: &World) -> Light {
+        *world.light_sources().first().unwrap()
+    }
+
+    #[test]
+    fn no_shadow_when_nothing_is_collinear_with_point_and_light() {
+        let w = World::default();
+        let p = Point::new(0.0, 10.0, 0.0);
+        assert_eq!(w.light_visibility(&light_at(&w), p, 0.0, 0), Color::white());
+    }
+
+    #[test]
+    fn shadow_when_object_is_between_point_and_light() {
+        let w = World::default();
+        let p = Point::new(10.0, -10.0, 10.0);
+        assert_eq!(w.light_visibility(&light_at(&w), p, 0.0, 0), Color::black());
+    }
+
+    #[test]
+    fn no_shadow_when_object_is_behind_light() {
+        let w = World::default();
+        let p = Point::new(-20.0, 20.0, -20.0);
+        assert_eq!(w.light_visibility(&light_at(&w), p, 0.0, 0), Color::white());
+    }
+
+    #[test]
+    fn no_shadow_when_object_is_behind_point() {
+        let w = World::default();
+        let p = Point::new(-2.0, 2.0, -2.0);
+        assert_eq!(w.light_visibility(&light_at(&w), p, 0.0, 0), Color::white());
+    }
+
+    #[test]
+    fn directional_light_is_blocked_at_any_distance() {
+        let w = World::default();
+        let sun = Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::white());
+        assert_eq!(
+            w.light_visibility(&sun, Point::new(0.0, -50.0, 0.0), 0.0, 0),
+            Color::black()
+        );
+        assert_eq!(
+            w.light_visibility(&sun, Point::new(5.0, -50.0, 0.0), 0.0, 0),
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn shade_hit_is_given_an_intersection_in_shadow() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 0.0, -10.0), Color::white()));
+        w.push_object(Object::sphere());
+        let mut s2 = Object::sphere();
+        s2.set_transformation(Transformations::translate(0.0, 0.0, 10.0));
+        w.push_object(s2);
+
+        let r = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        let i = Intersection::new(4.0, &w.objects()[1]);
+        let comps = IntersectionState::new(i, r);
+
+        assert_eq!(w.shade_hit(comps), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn area_light_casts_partial_shadow_at_umbra_edge() {
+        let w = World::default();
+        let light = Light::from(AreaLight::rectangle(
+            Point::new(-1.0, 10.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            8,
+            Vec3::new(0.0, 0.0, 2.0),
+            8,
+            Color::white(),
+        ));
+
+        let lit = w.light_visibility(&light, Point::new(5.0, -5.0, 0.0), 0.0, 0);
+        let umbra = w.light_visibility(&light, Point::new(0.0, -5.0, 0.0), 0.0, 0);
+        let penumbra = w.light_visibility(&light, Point::new(1.5, -5.0, 0.0), 0.0, 0);
+
+        assert_eq!(lit, Color::white());
+        assert_eq!(umbra, Color::black());
+        assert!(penumbra.r > 0.0 && penumbra.r < 1.0);
+    }
+
+    #[test]
+    fn area_light_jitter_varies_with_the_sample_seed() {
+        let w = World::default();
+        let light = Light::from(AreaLight::rectangle(
+            Point::new(-1.0, 10.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            4,
+            Vec3::new(0.0, 0.0, 2.0),
+            4,
+            Color::white(),
+        ));
+        let penumbra = Point::new(1.5, -5.0, 0.0);
+
+        let mut seen: Vec<f64> = (0..8)
+            .map(|seed| w.light_visibility(&light, penumbra, 0.0, seed).r)
+            .collect();
+        seen.sort_by(f64::total_cmp);
+        seen.dedup();
+        assert!(seen.len() > 1);
+    }
+
+    #[test]
+    fn shade_hit_adds_emission_even_without_lights() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(Material {
+            emission: Color::new(1.0, 0.5, 0.0),
+            emission_strength: 2.0,
+            ..Default::default()
+        }));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert_eq!(w.color_at(r), Color::new(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn emissive_objects_are_listed_as_emitters() {
+        let mut w = World::default();
+        assert!(w.emitters().is_empty());
+
+        let lamp = Object::sphere().with_material(Material {
+            emission: Color::white(),
+            ..Default::default()
+        });
+        w.push_object(lamp);
+
+        assert_eq!(w.emitters().len(), 1);
+        assert_eq!(*w.emitters()[0], lamp);
+    }
+
+    fn mirror() -> Material {
+        Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            reflective: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reflected_color_for_nonreflective_material() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut shape = w.objects()[1];
+        shape.set_material(Material {
+            ambient: 1.0,
+            ..shape.material()
+        });
+        let i = Intersection::new(1.0, &shape);
+        let comps = IntersectionState::new(i, r);
+
+        assert_eq!(
+            w.reflected_color(&comps, MAX_RECURSION_DEPTH, true),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn reflected_color_at_maximum_recursion_depth() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(mirror()));
+        w.set_background(Background::Solid(Color::green()));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = w.intersect_world(&r);
+        let comps = IntersectionState::prepare(xs[0], r, &xs);
+
+        assert_eq!(w.reflected_color(&comps, 0, true), Color::black());
+    }
+
+    #[test]
+    fn mutually_reflective_surfaces_terminate() {
+        let mut w = World::new();
+        let mut outer = Object::sphere().with_material(mirror());
+        outer.set_transformation(Transformations::scale(10.0, 10.0, 10.0));
+        w.push_object(outer);
+        w.push_light_source(Light::point(Point::zero(), Color::white()));
+
+        let r = Ray::new(Point::zero(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(r), Color::black());
+    }
+
+    #[test]
+    fn miss_returns_background() {
+        let mut w = World::default();
+        w.set_background(Background::Gradient {
+            bottom: Color::black(),
+            top: Color::white(),
+        });
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(w.color_at(r), Color::white());
+    }
+
+    #[test]
+    fn reflected_miss_returns_background() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(mirror()));
+        w.set_background(Background::Gradient {
+            bottom: Color::red(),
+            top: Color::blue(),
+        });
+
+        // straight back toward -z after bouncing off the front of the sphere
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(r), Color::new(0.5, 0.0, 0.5));
+
+        // off the top of the sphere toward +y
+        let r = Ray::new(
+            Point::new(0.0, f64::sqrt(2.0) / 2.0, -5.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert_eq!(w.color_at(r), Color::blue());
+    }
+
+    #[test]
+    fn refracted_color_with_opaque_surface() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let xs = w.intersect_world(&r);
+        let comps = IntersectionState::prepare(xs[0], r, &xs);
+
+        assert_eq!(w.refracted_color(&comps, 5, true), Color::black());
+    }
+
+    #[test]
+    fn refracted_color_under_total_internal_reflection() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        }));
+        let r = Ray::new(
+            Point::new(0.0, 0.0, f64::sqrt(2.0) / 2.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let xs = w.intersect_world(&r);
+        let comps = IntersectionState::prepare(xs[1], r, &xs);
+
+        assert_eq!(w.refracted_color(&comps, 5, true), Color::black());
+    }
+
+    #[test]
+    fn refracted_miss_returns_background() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        }));
+        w.set_background(Background::Solid(Color::new(0.2, 0.4, 0.6)));
+
+        // through the center of the sphere, so the ray isn't bent
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(r), Color::new(0.2, 0.4, 0.6));
+    }
+
+    // a single sphere of `material` under a red to blue gradient sky
+    fn gradient_world(material: Material) -> World {
+        let mut w = World::new();
+        w.set_background(Background::Gradient {
+            bottom: Color::red(),
+            top: Color::blue(),
+        });
+        w.push_object(Object::sphere().with_material(material));
+        w
+    }
+
+    #[test]
+    fn rough_mirror_blurs_the_reflection() {
+        // straight down onto the top of the sphere, the sharp reflection sees only the top
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(gradient_world(mirror()).color_at(r), Color::blue());
+
+        let rough = gradient_world(Material {
+            roughness: 0.4,
+            ..mirror()
+        });
+        let c = rough.color_at(r);
+        assert!(c.r > 0.0 && c.b > c.r);
+    }
+
+    #[test]
+    fn glossy_samples_are_taken_at_any_starting_depth() {
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rough = |samples| {
+            gradient_world(Material {
+                roughness: 0.4,
+                glossy_samples: samples,
+                ..mirror()
+            })
+        };
+        let averaged = rough(64).color_at_with_depth(r, 3);
+        assert_eq!(averaged, rough(64).color_at(r));
+        assert_ne!(averaged, rough(1).color_at_with_depth(r, 3));
+    }
+
+    #[test]
+    fn frosted_glass_blurs_the_refraction() {
+        let glass = Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        };
+        // up through the center, the sharp ray leaves the sphere unbent
+        let r = Ray::new(Point::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(gradient_world(glass).color_at(r), Color::blue());
+
+        let frosted = gradient_world(Material {
+            roughness: 0.3,
+            ..glass
+        });
+        let c = frosted.color_at(r);
+        assert!(c.r > 0.0 && c.b > c.r);
+    }
+
+    #[test]
+    fn thick_glass_absorbs_more_than_thin_glass() {
+        let tinted = |radius: f64| {
+            let mut w = World::new();
+            w.set_background(Background::Solid(Color::white()));
+            let mut s = Object::sphere().with_material(Material {
+                color: Color::black(),
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                refractive_index: 1.5,
+                absorption: Color::new(0.0, 0.2, 0.4),
+                ..Default::default()
+            });
+            s.set_transformation(Transformations::scale(radius, radius, radius));
+            w.push_object(s);
+            // straight through the center, crossing 2 * radius of glass
+            w.color_at(Ray::new(
+                Point::new(0.0, 0.0, -5.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ))
+        };
+
+        let thin = tinted(0.5);
+        assert_eq!(thin, Color::new(1.0, f64::exp(-0.2), f64::exp(-0.4)));
+        let thick = tinted(2.0);
+        assert_eq!(thick, Color::new(1.0, f64::exp(-0.8), f64::exp(-1.6)));
+    }
+
+    #[test]
+    fn fog_dims_with_distance() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::white()));
+        w.push_object(Object::sphere().with_material(Material {
+            emission: Color::white(),
+            ..Default::default()
+        }));
+        w.set_fog(Medium::new(Color::black(), Color::new(0.1, 0.2, 0.0)));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            w.color_at(r),
+            Color::new(f64::exp(-0.4), f64::exp(-0.8), 1.0)
+        );
+
+        // endless fog swallows the background in the channels it absorbs
+        let miss = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(miss), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn fog_scatters_light_towards_the_eye() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 2.0, 0.0), Color::white()));
+        w.set_fog(Medium::new(Color::new(0.05, 0.05, 0.05), Color::black()));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let c = w.color_at(r);
+        assert!(c.r > 0.0 && c.r.approx_eq(c.b));
+    }
+
+    #[test]
+    fn volume_boundary_is_invisible() {
+        let mut w = World::new();
+        w.set_background(Background::Solid(Color::new(0.2, 0.4, 0.6)));
+        w.push_volume(Volume::new(
+            Object::sphere(),
+            Medium::new(Color::black(), Color::black()),
+        ));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(r), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn volumes_are_lit_only_where_the_light_reaches() {
+        let smoke = Medium::new(Color::new(0.5, 0.5, 0.5), Color::black());
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+        w.push_volume(Volume::new(Object::sphere(), smoke));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(w.color_at(r).g > 0.0);
+        // only the part of the ray inside the volume scatters
+        let beside = Ray::new(Point::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(beside), Color::black());
+
+        // a shell around the light puts the whole volume in shadow
+        let mut shell = Object::sphere();
+        shell.set_transformation(Transformations::translate(0.0, 10.0, 0.0));
+        w.push_object(shell);
+        assert_eq!(w.color_at(r), Color::black());
+    }
+
+    #[test]
+    fn media_dim_the_light_reaching_surfaces() {
+        let mut w = World::new();
+        let light = Light::point(Point::new(0.0, 10.0, 0.0), Color::white());
+        let mut smoke = Object::sphere();
+        smoke.set_transformation(Transformations::translate(0.0, 5.0, 0.0));
+        w.push_volume(Volume::new(
+            smoke,
+            Medium::new(Color::black(), Color::new(1.0, 1.0, 1.0)),
+        ));
+        let p = Point::new(0.0, -1.0, 0.0);
+
+        let through_smoke = w.light_visibility(&light, p, 0.0, 0);
+        assert!(through_smoke.r.approx_eq(f64::exp(-2.0)));
+
+        w.set_fog(Medium::new(Color::black(), Color::new(0.1, 0.0, 0.0)));
+        let through_fog = w.light_visibility(&light, p, 0.0, 0);
+        assert!(through_fog.r.approx_eq(f64::exp(-3.1)));
+        assert!(through_fog.g.approx_eq(f64::exp(-2.0)));
+    }
+
+    #[test]
+    fn fog_jitter_differs_between_pixels() {
+        let mut w = World::new();
+        w.push_light_source(Light::point(Point::new(0.0, 2.0, 0.0), Color::white()));
+        w.set_fog(Medium::new(Color::new(0.05, 0.05, 0.05), Color::black()).with_steps(2));
+        let origin = Point::new(0.0, 0.0, -5.0);
+
+        // rays leaving the same camera in mirrored directions see mirrored fog, unless the
+        // march is jittered differently
+        let left = w.color_at(Ray::new(origin, Vec3::new(-0.1, 0.0, 1.0)));
+        let right = w.color_at(Ray::new(origin, Vec3::new(0.1, 0.0, 1.0)));
+        assert_ne!(left, right);
+        let ray = Ray::new(origin, Vec3::new(0.1, 0.0, 1.0));
+        assert_ne!(w.color_at(ray.with_seed(1)), w.color_at(ray.with_seed(2)));
+    }
+
+    #[test]
+    fn subsurface_scattering_lets_light_through_to_the_dark_side() {
+        let lit_from_above = |subsurface: Option<Subsurface>| {
+            let mut w = World::new();
+            w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+            w.push_object(Object::sphere().with_material(Material {
+                color: Color::new(0.9, 0.6, 0.4),
+                ambient: 0.0,
+                specular: 0.0,
+                subsurface,
+                ..Default::default()
+            }));
+            // looking at the bottom of the sphere, facing away from the light
+            w.color_at(Ray::new(
+                Point::new(0.0, -5.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ))
+        };
+
+        assert_eq!(lit_from_above(None), Color::black());
+        let wax = Subsurface::new(Color::new(1.0, 0.6, 0.3)).with_samples(64);
+        let c = lit_from_above(Some(wax));
+        assert!(c.r > 0.0 && c.r > c.b);
+    }
+
+    #[test]
+    fn microfacet_materials_ignore_subsurface_scattering() {
+        let lit = |subsurface: Option<Subsurface>| {
+            let mut w = World::new();
+            w.push_light_source(Light::point(Point::new(0.0, 10.0, 0.0), Color::white()));
+            w.push_object(Object::sphere().with_material(Material {
+                color: Color::new(0.9, 0.6, 0.4),
+                ambient: 0.0,
+                subsurface,
+                model: ShadingModel::Microfacet {
+                    metallic: 0.0,
+                    roughness: 0.5,
+                },
+                ..Default::default()
+            }));
+            w.color_at(Ray::new(
+                Point::new(0.0, 5.0, -1.0),
+                Vec3::new(0.0, -1.0, 0.2),
+            ))
+        };
+        let wax = Subsurface::new(Color::new(1.0, 0.6, 0.3));
+        assert_eq!(lit(Some(wax)), lit(None));
+    }
+
+    #[test]
+    fn dispersion_splits_light_into_colors() {
+        let prism = Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            dispersion: Dispersion::Cauchy { a: 1.5, b: 0.05 },
+            ..Default::default()
+        };
+        let w = gradient_world(prism);
+
+        // off center, so the ray is bent on the way through
+        let r = Ray::new(Point::new(0.0, 0.6, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let blue = w.color_at(r.with_wavelength(Some(450.0)));
+        let red = w.color_at(r.with_wavelength(Some(650.0)));
+        assert!((blue.b - red.b).abs() > 0.01, "{blue:?} {red:?}");
+
+        // without dispersion every wavelength leaves the same way
+        let plain = gradient_world(Material {
+            dispersion: Dispersion::None,
+            ..prism
+        });
+        assert_eq!(
+            plain.color_at(r.with_wavelength(Some(450.0))),
+            plain.color_at(r.with_wavelength(Some(650.0)))
+        );
+    }
+
+    #[test]
+    fn shade_hit_with_reflective_transparent_material_uses_schlick() {
+        let mut w = World::new();
+        w.push_object(Object::sphere().with_material(Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            reflective: 1.0,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        }));
+        w.set_background(Background::Solid(Color::white()));
+
+        // the straight-through ray loses 4% at each glass surface
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let c = w.color_at(r);
+        assert!(c.r > 0.9 && c.r < 1.0 + 1e-9);
+    }
+
+    #[test]
+    fn environment_light_illuminates_and_is_shadowed() {
+        let mut w = World::new();
+        w.push_object(Object::sphere());
+        let mut sky = Canvas::new(16, 8);
+        // bright upper hemisphere, dark lower one
+        for y in 0..4 {
+            for x in 0..16 {
+                sky[y][x] = Color::white();
+            }
+        }
+        w.set_environment_light(EnvironmentLight::new(sky, 64));
+
+        let top = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let bottom = Ray::new(Point::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        assert!(w.color_at(top).r > 0.5);
+        assert_eq!(w.color_at(bottom), Color::black());
+
+        // a second sphere above blocks most of the sky for the first one
+        let mut lid = Object::sphere();
+        lid.set_transformation(
+            Transformations::translate(0.0, 3.0, 0.0) * Transformations::scale(2.0, 0.5, 2.0),
+        );
+        w.push_object(lid);
+        let under_lid = w.objects()[0];
+        let state = IntersectionState::new(
+            Intersection::new(4.0, &under_lid),
+            Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+        );
+        let shaded = w.shade_hit(state);
+        let state = IntersectionState::new(
+            Intersection::new(4.0, &under_lid),
+            Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+        );
+        let mut open = World::new();
+        open.push_object(under_lid);
+        open.set_environment_light(w.environment_light().unwrap().clone());
+
+        assert!(shaded.r < open.shade_hit(state).r);
+    }
 }
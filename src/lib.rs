@@ -5,17 +5,30 @@ pub mod primitives {
     pub mod matrix3f;
     pub mod matrix4f;
     pub mod point;
+    pub mod quaternion;
     pub mod tuple;
     pub mod vec3;
 }
 
 pub mod core {
+    pub mod background;
     pub mod camera;
     pub mod canvas;
+    pub mod environment;
+    pub mod integrator;
     pub mod light;
+    pub mod medium;
     pub mod object;
+    pub mod photon_map;
+    pub mod random;
     pub mod ray;
+    pub mod sampling;
+    pub mod shading;
+    pub mod sky;
+    pub mod spectrum;
     pub mod sphere;
+    pub mod subsurface;
+    pub mod tone_mapping;
     pub mod transforms;
     pub mod world;
 }
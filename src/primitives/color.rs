@@ -40,6 +40,11 @@ impl Color {
     pub fn white() -> Color {
         return Color::new(1.0, 1.0, 1.0);
     }
+
+    // relative luminance of linear Rec. 709 / sRGB primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
 }
 
 impl Add for Color {
@@ -183,4 +188,11 @@ mod tests {
         let expected = Color::new(0.125, 0.245, 0.02);
         assert_eq!(c1 * c2, expected);
     }
+
+    #[test]
+    fn luminance_weights_green_highest() {
+        assert!(Color::white().luminance().approx_eq(1.0));
+        assert!(Color::green().luminance() > Color::red().luminance());
+        assert!(Color::red().luminance() > Color::blue().luminance());
+    }
 }
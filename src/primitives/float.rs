@@ -1,5 +1,7 @@
 pub const EPSILON: f64 = 1.0e-7;
 pub const LOW_EPSILON: f64 = 1.0e-3;
+// offset along the normal that keeps secondary rays from hitting their own surface
+pub const SURFACE_EPSILON: f64 = 1.0e-5;
 
 use float_cmp::approx_eq;
 
@@ -0,0 +1,160 @@
+use super::{float::ApproxEq, matrix4f::Matrix4f};
+
+// unit quaternion representing a rotation, w is the scalar part
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // rotation part of `m`, which must be orthonormal in its upper 3x3 block
+    pub fn from_rotation_matrix(m: &Matrix4f) -> Self {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+        let q = if trace > 0.0 {
+            let s = f64::sqrt(trace + 1.0) * 2.0;
+            Quaternion::new(
+                0.25 * s,
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(1, 0)] - m[(0, 1)]) / s,
+            )
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = f64::sqrt(1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]) * 2.0;
+            Quaternion::new(
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                0.25 * s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+            )
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = f64::sqrt(1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]) * 2.0;
+            Quaternion::new(
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                0.25 * s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+            )
+        } else {
+            let s = f64::sqrt(1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]) * 2.0;
+            Quaternion::new(
+                (m[(1, 0)] - m[(0, 1)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+                0.25 * s,
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4f {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix4f::new_from([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        Quaternion::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    // spherical linear interpolation along the shortest arc
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Self {
+        let mut end = *other;
+        let mut cos_theta = self.dot(other);
+        if cos_theta < 0.0 {
+            end = Quaternion::new(-end.w, -end.x, -end.y, -end.z);
+            cos_theta = -cos_theta;
+        }
+
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                f64::sin((1.0 - t) * theta) / sin_theta,
+                f64::sin(t * theta) / sin_theta,
+            )
+        };
+
+        Quaternion::new(
+            a * self.w + b * end.w,
+            a * self.x + b * end.x,
+            a * self.y + b * end.y,
+            a * self.z + b * end.z,
+        )
+        .normalize()
+    }
+}
+
+// q and -q are the same rotation
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Self) -> bool {
+        self.dot(other).abs().approx_eq_low_precision(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transforms::Transformations;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    #[test]
+    fn quaternion_round_trips_rotation_matrices() {
+        let rotations = [
+            Transformations::rotate_x(FRAC_PI_4),
+            Transformations::rotate_y(PI),
+            Transformations::rotate_z(-FRAC_PI_2) * Transformations::rotate_x(1.0),
+            Matrix4f::identity(),
+        ];
+        for m in rotations {
+            assert_eq!(Quaternion::from_rotation_matrix(&m).to_matrix(), m);
+        }
+    }
+
+    #[test]
+    fn slerp_halfway_is_half_the_angle() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_rotation_matrix(&Transformations::rotate_y(FRAC_PI_2));
+        let half = a.slerp(&b, 0.5);
+
+        assert_eq!(half.to_matrix(), Transformations::rotate_y(FRAC_PI_4));
+        assert_eq!(a.slerp(&b, 0.0), a);
+        assert_eq!(a.slerp(&b, 1.0), b);
+    }
+}
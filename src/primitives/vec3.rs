@@ -50,6 +50,18 @@ impl Vec3 {
     pub fn reflect(in_v: Vec3, normal: Vec3) -> Vec3 {
         return in_v - normal * 2.0 * (in_v ^ normal);
     }
+
+    // Snell's law for `eyev` pointing away from the surface and `n_ratio` = n1 / n2,
+    // None under total internal reflection
+    pub fn refract(eyev: Vec3, normal: Vec3, n_ratio: f64) -> Option<Vec3> {
+        let cos_i = eyev ^ normal;
+        let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = f64::sqrt(1.0 - sin2_t);
+        Some(normal * (n_ratio * cos_i - cos_t) - eyev * n_ratio)
+    }
 }
 
 impl PartialEq for Vec3 {
@@ -238,4 +250,23 @@ mod tests {
         assert_eq!(cross ^ v1, 0.0);
         assert_eq!(cross ^ v2, 0.0);
     }
+
+    #[test]
+    fn refraction_bends_towards_the_normal() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let eyev = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        let t = Vec3::refract(eyev, n, 1.0 / 1.5).unwrap();
+        // sin(theta_t) = sin(45°) / 1.5
+        assert!((t.x - f64::sqrt(0.5) / 1.5).abs() < 1e-9);
+        assert!(t.y < 0.0);
+        assert!((t.magnitude() - 1.0).abs() < 1e-9);
+        assert_eq!(Vec3::refract(eyev, n, 1.0).unwrap(), -eyev);
+    }
+
+    #[test]
+    fn refraction_fails_past_the_critical_angle() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let eyev = Vec3::new(-1.0, 0.2, 0.0).normalize();
+        assert_eq!(Vec3::refract(eyev, n, 1.5), None);
+    }
 }